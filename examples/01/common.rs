@@ -1,59 +1,40 @@
+use std::{path::PathBuf, time::Instant};
 use wgpu::{
     IndexFormat,
     PrimitiveTopology,
     ShaderSource,
 };
+use crate::hot_reload::{self, ErrorCapture, ShaderWatcher};
+use crate::screenshot::{self, Capture};
+use crate::transforms::WgpuConfig;
+use crate::validate::{self, preprocess::Preprocessor};
 use winit::{
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::Window,
 };
 
 pub struct Inputs<'a> {
+    /// Names the shader in validation errors.
+    pub file: &'a str,
     pub source: ShaderSource<'a>,
     pub topology: PrimitiveTopology,
     pub strip_index_format: Option<IndexFormat>,
+    /// The file `source` came from. When set, `run` rebuilds the pipeline whenever it changes.
+    pub shader_path: Option<PathBuf>,
 }
 
-pub async fn run(event_loop: EventLoop<()>, window: Window, inputs: Inputs<'_>, num_vertices: u32) {
-    let size = window.inner_size();
-    let instance = wgpu::Instance::new(wgpu::Backends::METAL);
-    let surface = unsafe { instance.create_surface(&window) };
-    let adapter = instance
-        .request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::default(),
-            compatible_surface: Some(&surface),
-            force_fallback_adapter: false,
-        })
-        .await
-        .expect("Faled to find an appropiate adapter");
-
-    let (device, queue) = adapter
-        .request_device(
-            &wgpu::DeviceDescriptor {
-                label: None,
-                features: wgpu::Features::empty(),
-                limits: wgpu::Limits::default(),
-            },
-            None,
-        )
-        .await
-        .expect("Failed to create device");
-
-    let format = surface.get_preferred_format(&adapter).unwrap();
-    let mut config = wgpu::SurfaceConfiguration {
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-        format: format,
-        width: size.width,
-        height: size.height,
-        present_mode: wgpu::PresentMode::Mailbox,
-    };
-    surface.configure(&device, &config);
-
-    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
-        label: None,
-        source: inputs.source,
-    });
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    preprocessor: &Preprocessor,
+    file: &str,
+    source: ShaderSource,
+    topology: PrimitiveTopology,
+    strip_index_format: Option<IndexFormat>,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let ShaderSource::Wgsl(wgsl) = &source;
+    let shader = validate::shader_module_with(device, preprocessor, file, wgsl);
 
     let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: None,
@@ -61,7 +42,7 @@ pub async fn run(event_loop: EventLoop<()>, window: Window, inputs: Inputs<'_>,
         push_constant_ranges: &[],
     });
 
-    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: None,
         layout: Some(&pipeline_layout),
         vertex: wgpu::VertexState {
@@ -75,18 +56,115 @@ pub async fn run(event_loop: EventLoop<()>, window: Window, inputs: Inputs<'_>,
             targets: &[format.into()],
         }),
         primitive: wgpu::PrimitiveState {
-            topology: inputs.topology,
-            strip_index_format: inputs.strip_index_format,
+            topology,
+            strip_index_format,
             ..Default::default()
         },
         depth_stencil: None,
         multisample: wgpu::MultisampleState::default(),
+    })
+}
+
+fn draw(encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, render_pipeline: &wgpu::RenderPipeline, num_vertices: u32) {
+    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: None,
+        color_attachments: &[wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color {r: 0.05, g: 0.062, b: 0.08, a: 1.0}),
+                store: true,
+            },
+        }],
+        depth_stencil_attachment: None,
     });
+    rpass.set_pipeline(render_pipeline);
+    rpass.draw(0..num_vertices, 0..1);
+}
+
+/// Opens the window and draws until it is closed. `p` saves a screenshot to the working
+/// directory; with `screenshot` set the first frame is saved there and the window closes.
+/// With `inputs.shader_path` set, edits to the shader are picked up while the window is open;
+/// a shader that fails to compile is logged and the previous one keeps drawing.
+pub async fn run(
+    event_loop: EventLoop<()>,
+    window: Window,
+    inputs: Inputs<'_>,
+    num_vertices: u32,
+    wgpu_config: &WgpuConfig,
+    screenshot: Option<PathBuf>,
+) {
+    let size = window.inner_size();
+    let instance = wgpu::Instance::new(wgpu_config.backends);
+    let surface = unsafe { instance.create_surface(&window) };
+    let adapter = wgpu_config
+        .request_adapter(&instance, Some(&surface))
+        .await
+        .expect("Failed to find an appropriate adapter");
+
+    let (device, queue) = wgpu_config
+        .request_device(&adapter)
+        .await
+        .expect("Failed to create device");
 
+    let format = surface.get_preferred_format(&adapter).unwrap();
+    let mut config = wgpu::SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        format,
+        width: size.width,
+        height: size.height,
+        present_mode: wgpu::PresentMode::Mailbox,
+    };
+    surface.configure(&device, &config);
+
+    let (topology, strip_index_format) = (inputs.topology, inputs.strip_index_format);
+    let file = inputs.file.to_string();
+    let mut render_pipeline = create_render_pipeline(&device, &Preprocessor::new(), &file, inputs.source, topology, strip_index_format, format);
+    let mut watcher = inputs.shader_path.as_ref().map(|path| (ShaderWatcher::with_includes([path]), ErrorCapture::install(&device)));
+
+    let mut screenshot_path = screenshot;
+    let exit_after_screenshot = screenshot_path.is_some();
     event_loop.run(move |event, _, control_flow| {
-        let _ = (&instance, &adapter, &shader, &pipeline_layout);
-        *control_flow = ControlFlow::Wait;
+        let _ = (&instance, &adapter);
+        *control_flow = match watcher {
+            Some(_) => ControlFlow::WaitUntil(Instant::now() + hot_reload::POLL_INTERVAL),
+            None => ControlFlow::Wait,
+        };
         match event {
+            Event::MainEventsCleared => {
+                if let Some((watcher, errors)) = &mut watcher {
+                    if watcher.changed() {
+                        let path = inputs.shader_path.as_ref().unwrap();
+                        let preprocessor = watcher.preprocessor();
+                        let reloaded = hot_reload::read_source(preprocessor, path).and_then(|source| {
+                            let source = ShaderSource::Wgsl(source.into());
+                            errors.capture(|| create_render_pipeline(&device, preprocessor, &file, source, topology, strip_index_format, format))
+                        });
+                        match reloaded {
+                            Ok(pipeline) => {
+                                render_pipeline = pipeline;
+                                println!("reloaded {}", path.display());
+                                window.request_redraw();
+                            }
+                            Err(e) => log::error!("keeping the previous shader, {} failed:\n{}", path.display(), e),
+                        }
+                    }
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::KeyboardInput {
+                    input: KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::P),
+                        ..
+                    },
+                    ..
+                },
+                ..
+            } => {
+                screenshot_path = Some(screenshot::next_path("screenshot"));
+                window.request_redraw();
+            }
             Event::WindowEvent {
                 event: WindowEvent::Resized(size),
                 ..
@@ -100,24 +178,24 @@ pub async fn run(event_loop: EventLoop<()>, window: Window, inputs: Inputs<'_>,
                 let view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
                 let mut encoder =
                     device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-                {
-                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                        label: None,
-                        color_attachments: &[wgpu::RenderPassColorAttachment {
-                            view: &view,
-                            resolve_target: None,
-                            ops: wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(wgpu::Color {r: 0.05, g: 0.062, b: 0.08, a: 1.0}),
-                                store: true,
-                            },
-                        }],
-                        depth_stencil_attachment: None,
-                    });
-                    rpass.set_pipeline(&render_pipeline);
-                    rpass.draw(0..num_vertices, 0..1);
-                }
+                draw(&mut encoder, &view, &render_pipeline, num_vertices);
+                // the surface texture cannot be copied from, so draw the frame again into one that can
+                let capture = screenshot_path.take().map(|path| {
+                    let capture = Capture::new(&device, config.width, config.height, config.format);
+                    draw(&mut encoder, &capture.view, &render_pipeline, num_vertices);
+                    (capture, path)
+                });
                 queue.submit(Some(encoder.finish()));
                 frame.present();
+                if let Some((capture, path)) = capture {
+                    match pollster::block_on(capture.save_png(&device, &queue, &path)) {
+                        Ok(()) => println!("saved {}", path.display()),
+                        Err(e) => log::error!("{}", e),
+                    }
+                    if exit_after_screenshot {
+                        *control_flow = ControlFlow::Exit;
+                    }
+                }
             }
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
@@ -127,3 +205,68 @@ pub async fn run(event_loop: EventLoop<()>, window: Window, inputs: Inputs<'_>,
         }
     });
 }
+
+pub const HEADLESS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// A pipeline for `inputs` drawing into an offscreen texture, without a window.
+pub struct Headless {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    render_pipeline: wgpu::RenderPipeline,
+    capture: Capture,
+    num_vertices: u32,
+}
+
+impl Headless {
+    pub async fn new(
+        inputs: Inputs<'_>,
+        num_vertices: u32,
+        width: u32,
+        height: u32,
+        wgpu_config: &WgpuConfig,
+    ) -> anyhow::Result<Self> {
+        let instance = wgpu::Instance::new(wgpu_config.backends);
+        let adapter = wgpu_config.request_adapter(&instance, None).await?;
+        let (device, queue) = wgpu_config.request_device(&adapter).await?;
+
+        let render_pipeline = create_render_pipeline(
+            &device,
+            &Preprocessor::new(),
+            inputs.file,
+            inputs.source,
+            inputs.topology,
+            inputs.strip_index_format,
+            HEADLESS_FORMAT,
+        );
+
+        let capture = Capture::new(&device, width, height, HEADLESS_FORMAT);
+        Ok(Self { device, queue, render_pipeline, capture, num_vertices })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.capture.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.capture.height
+    }
+
+    /// Draws one frame and returns the tightly packed RGBA pixels.
+    pub async fn render(&self) -> anyhow::Result<Vec<u8>> {
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        draw(&mut encoder, &self.capture.view, &self.render_pipeline, self.num_vertices);
+        self.queue.submit(Some(encoder.finish()));
+        self.capture.read_rgba(&self.device, &self.queue).await
+    }
+}
+
+/// Renders `inputs` into an offscreen texture and returns the tightly packed RGBA pixels.
+pub async fn run_headless(
+    inputs: Inputs<'_>,
+    num_vertices: u32,
+    width: u32,
+    height: u32,
+    wgpu_config: &WgpuConfig,
+) -> anyhow::Result<Vec<u8>> {
+    Headless::new(inputs, num_vertices, width, height, wgpu_config).await?.render().await
+}
@@ -1,19 +1,96 @@
 mod common;
+#[path="../common/hot_reload.rs"]
+mod hot_reload;
+#[path="../common/screenshot.rs"]
+mod screenshot;
+#[path="../common/transforms.rs"]
+mod transforms;
+#[path="../common/validate.rs"]
+mod validate;
 
 use winit::event_loop::EventLoop;
 use std::borrow::Cow;
 
 fn main() {
-    let event_loop = EventLoop::new();
-    let window = winit::window::Window::new(&event_loop).unwrap();
-    window.set_title("Mine!");
     env_logger::init();
 
-    let inputs = common::Inputs {
+    let mut inputs = common::Inputs {
+        file: "shader.wgsl",
         source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
         topology: wgpu::PrimitiveTopology::TriangleList,
-        strip_index_format: None
+        strip_index_format: None,
+        shader_path: None,
+    };
+
+    let args: Vec<String> = std::env::args().collect();
+    let mut wgpu_config = transforms::WgpuConfig::from_env();
+    if args.len() > 1 && args[1] == "--list-adapters" {
+        for (i, info) in transforms::list_adapters(wgpu_config.backends).iter().enumerate() {
+            println!("[{}] {} ({:?}, {:?})", i, info.name, info.backend, info.device_type);
+        }
+        return;
+    }
+
+    // --check-shaders validates the WGSL of every example with naga, without a window or a GPU
+    if args.len() > 1 && args[1] == "--check-shaders" {
+        if !validate::check_shaders() {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if args.len() > 1 && args[1] == "--headless" {
+        wgpu_config.limits = wgpu::Limits::downlevel_defaults();
+        let mut path = "triangle.png";
+        if args.len() > 2 {
+            path = &args[2];
+        }
+        let (width, height) = (800, 600);
+        let pixels = pollster::block_on(common::run_headless(inputs, 3, width, height, &wgpu_config))
+            .expect("Failed to render offscreen");
+        image::save_buffer(path, &pixels, width, height, image::ColorType::Rgba8).unwrap();
+        println!("wrote {}", path);
+        return;
+    }
+
+    // --record <dir|file.gif> [--frames N] [--fps F] renders N frames offscreen; the triangle does
+    // not move, but the sequence is the same on every run
+    if args.len() > 2 && args[1] == "--record" {
+        wgpu_config.limits = wgpu::Limits::downlevel_defaults();
+        let mut frames = 60;
+        let mut fps = 30;
+        for pair in args[3..].chunks(2) {
+            match pair {
+                [flag, value] if flag == "--frames" => frames = value.parse().expect("--frames takes a count"),
+                [flag, value] if flag == "--fps" => fps = value.parse().expect("--fps takes a rate"),
+                _ => panic!("usage: --record <dir|file.gif> [--frames N] [--fps F]"),
+            }
+        }
+        let headless = pollster::block_on(common::Headless::new(inputs, 3, 800, 600, &wgpu_config))
+            .expect("Failed to render offscreen");
+        let mut recording = screenshot::Recording::new(&args[2], fps).unwrap();
+        for _ in 0..frames {
+            let pixels = pollster::block_on(headless.render()).expect("Failed to render offscreen");
+            recording.push(pixels, headless.width(), headless.height()).unwrap();
+        }
+        println!("wrote {} frames to {}", recording.frames(), args[2]);
+        return;
+    }
+
+    // --screenshot [path] shows the window for one frame and saves it, unlike --headless
+    let screenshot = match args.get(1).map(String::as_str) {
+        Some("--screenshot") => Some(args.get(2).map_or("screenshot.png", String::as_str).into()),
+        _ => None,
     };
 
-    pollster::block_on(common::run(event_loop, window, inputs, 3));
+    // --hot-reload redraws with shader.wgsl from the source tree whenever it is saved
+    if args.len() > 1 && args[1] == "--hot-reload" {
+        inputs.shader_path = Some(concat!(env!("CARGO_MANIFEST_DIR"), "/examples/01/shader.wgsl").into());
+    }
+
+    let event_loop = EventLoop::new();
+    let window = winit::window::Window::new(&event_loop).unwrap();
+    window.set_title("Mine!");
+
+    pollster::block_on(common::run(event_loop, window, inputs, 3, &wgpu_config, screenshot));
 }
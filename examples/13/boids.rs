@@ -1,34 +1,255 @@
 #![allow(dead_code)]
-use bytemuck;
-use std:: { iter, mem };
-use wgpu::util::DeviceExt;
+use std:: { iter, path::{Path, PathBuf}, time::SystemTime };
 use winit::{
     event::*,
     window::Window,
     event_loop::{ControlFlow, EventLoop},
     window::WindowBuilder,
 };
-use rand::{
-    distributions::{Distribution, Uniform},
-    SeedableRng,
-};
 
+mod boids_render;
+mod boids_sim;
+mod trajectory;
+#[path="../common/compute.rs"]
+mod compute;
+#[path="../common/hot_reload.rs"]
+mod hot_reload;
+#[path="../common/reflect.rs"]
+mod reflect;
+#[path="../common/screenshot.rs"]
+mod screenshot;
 #[path="../common/transforms.rs"]
 mod transforms;
+#[path="../common/validate.rs"]
+mod validate;
+
+const SEED: u64 = 42;
+/// Workgroup sizes the w key cycles through.
+const WORKGROUP_SIZES: [u32; 4] = [32, 64, 128, 256];
+/// Multipliers the 1/2/3 keys cycle each rule's scale through.
+const RULE_WEIGHTS: [f32; 5] = [1.0, 2.0, 4.0, 0.0, 0.5];
+const ATTRACTOR_STRENGTH: f32 = 0.005;
+/// The render and grid shaders `--hot-reload` watches, in that order, besides their includes.
+const SHADER_PATHS: [&str; 2] = [
+    concat!(env!("CARGO_MANIFEST_DIR"), "/examples/13/boids.wgsl"),
+    concat!(env!("CARGO_MANIFEST_DIR"), "/examples/13/boids_grid.wgsl"),
+];
+
+const CONTROLS: &str = "\
+space       pause / resume
+. (period)  advance a single step
+r           reset to the seeded initial state or the loaded snapshot
+up / down   speed the time step up / down
+1 / 2 / 3   cycle the cohesion / separation / alignment weight
+left click  add an attractor, right click: add a repulsor
+c           clear attractors and repulsors
+b           cycle the boundary mode: wrap, reflect, steer
+s           save a snapshot to boids_FRAME.snap (.json with --save-json)
+p           save a screenshot to boids_NNN.png
++ / -       double / halve the particle count and restart
+w           cycle the compute workgroup size";
+
+/// Usage: boids [COLOR_SCALE] [--particles N] [--workgroup-size N] [--grid] [--params FILE.json]
+///              [--delta-t X] [--rule1-distance X] ... [--boundary wrap|reflect|steer]
+///              [--obstacle circle:x,y,r] [--obstacle box:x,y,w,h] [--sdf MASK.png]
+///              [--species FILE.json] [--seed N] [--snapshot FILE] [--save-json]
+///              [--trajectory FILE.csv|FILE.npy] [--trajectory-every N]
+///              [--screenshot FILE.png] [--screenshot-frame N]
+///              [--record DIR|FILE.gif] [--frames N] [--fps F] [--hot-reload]
+///        boids --check-shaders
+///
+/// `--obstacle` can be repeated. `--sdf` takes an image whose dark pixels are obstacles,
+/// stretched over the whole [-1, 1] square. `--species` splits the boids into species with
+/// their own colors and interaction weights, see boids_species.json for predators and prey.
+/// `--snapshot` starts from a saved snapshot instead of the seeded random distribution, with
+/// its parameters in place of the defaults; flags after it still override them.
+/// `--trajectory` streams the positions and velocities after every Nth step to a file until the
+/// window is closed or the particle count changes. `--screenshot` saves the frame drawn after
+/// step N (1 by default) and exits. `--record` renders N frames (300 by default) offscreen
+/// without showing the window, one simulation step each, and writes them as numbered PNGs into
+/// DIR or as a GIF playing at F frames per second (30 by default). `--hot-reload` rebuilds the
+/// pipelines whenever boids.wgsl, boids_grid.wgsl or a snippet they include changes; a shader that
+/// fails to compile is logged and the previous pipelines keep running. `--check-shaders`
+/// validates the WGSL of every example and exits without opening a window.
+///
+/// Flags given after `--params` override values from the file. The file is re-read whenever it
+/// changes on disk, so rule distances and scales can be tuned while the simulation runs; a reload
+/// takes the file's values as they are.
+struct Options {
+    color_scale: f32,
+    num_particles: u32,
+    workgroup_size: u32,
+    grid: bool,
+    params: boids_sim::SimParams,
+    params_path: Option<PathBuf>,
+    obstacles: boids_sim::Obstacles,
+    sdf_path: Option<PathBuf>,
+    species: boids_sim::SpeciesConfig,
+    seed: u64,
+    snapshot: Option<boids_sim::Snapshot>,
+    save_json: bool,
+    trajectory: Option<PathBuf>,
+    trajectory_every: usize,
+    screenshot: Option<PathBuf>,
+    screenshot_frame: usize,
+    record: Option<PathBuf>,
+    record_frames: usize,
+    record_fps: u32,
+    hot_reload: bool,
+    check_shaders: bool,
+}
 
-const NUM_PARTICLES: u32 = 5000;
-const PARTICLES_PER_GROUP: u32 = 64;
+impl Options {
+    fn from_args() -> anyhow::Result<Self> {
+        let mut options = Self {
+            color_scale: 0.1,
+            num_particles: 5000,
+            workgroup_size: boids_sim::Simulation::DEFAULT_WORKGROUP_SIZE,
+            grid: false,
+            params: boids_sim::SimParams::default(),
+            params_path: None,
+            obstacles: boids_sim::Obstacles::default(),
+            sdf_path: None,
+            species: boids_sim::SpeciesConfig::default(),
+            seed: SEED,
+            snapshot: None,
+            save_json: false,
+            trajectory: None,
+            trajectory_every: 1,
+            screenshot: None,
+            screenshot_frame: 1,
+            record: None,
+            record_frames: 300,
+            record_fps: 30,
+            hot_reload: false,
+            check_shaders: false,
+        };
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        let mut i = 0;
+        while i < args.len() {
+            let value = args.get(i + 1).map(String::as_str).unwrap_or_default();
+            match args[i].as_str() {
+                "--grid" => options.grid = true,
+                "--particles" => {
+                    options.num_particles = value.parse()?;
+                    if options.num_particles == 0 {
+                        anyhow::bail!("--particles must be at least 1");
+                    }
+                    i += 1;
+                }
+                "--workgroup-size" => {
+                    options.workgroup_size = value.parse()?;
+                    if !(1..=boids_sim::Simulation::MAX_WORKGROUP_SIZE).contains(&options.workgroup_size) {
+                        anyhow::bail!("--workgroup-size must be between 1 and {}", boids_sim::Simulation::MAX_WORKGROUP_SIZE);
+                    }
+                    i += 1;
+                }
+                "--params" => {
+                    options.params = boids_sim::SimParams::load(value)?;
+                    options.params_path = Some(PathBuf::from(value));
+                    i += 1;
+                }
+                "--obstacle" => {
+                    options.obstacles.push(boids_sim::Obstacle::parse(value)?)?;
+                    i += 1;
+                }
+                "--save-json" => options.save_json = true,
+                "--hot-reload" => options.hot_reload = true,
+                "--check-shaders" => options.check_shaders = true,
+                "--trajectory" => {
+                    options.trajectory = Some(PathBuf::from(value));
+                    i += 1;
+                }
+                "--trajectory-every" => {
+                    options.trajectory_every = value.parse()?;
+                    i += 1;
+                }
+                "--screenshot" => {
+                    options.screenshot = Some(PathBuf::from(value));
+                    i += 1;
+                }
+                "--screenshot-frame" => {
+                    options.screenshot_frame = value.parse()?;
+                    i += 1;
+                }
+                "--record" => {
+                    options.record = Some(PathBuf::from(value));
+                    i += 1;
+                }
+                "--frames" => {
+                    options.record_frames = value.parse()?;
+                    i += 1;
+                }
+                "--fps" => {
+                    options.record_fps = value.parse()?;
+                    i += 1;
+                }
+                "--seed" => {
+                    options.seed = value.parse()?;
+                    i += 1;
+                }
+                "--snapshot" => {
+                    let snapshot = boids_sim::Snapshot::load(value)?;
+                    options.params = snapshot.params;
+                    options.num_particles = snapshot.particles.len() as u32;
+                    options.seed = snapshot.seed;
+                    options.snapshot = Some(snapshot);
+                    i += 1;
+                }
+                "--species" => {
+                    options.species = boids_sim::SpeciesConfig::load(value)?;
+                    i += 1;
+                }
+                "--sdf" => {
+                    options.sdf_path = Some(PathBuf::from(value));
+                    i += 1;
+                }
+                flag if flag.starts_with("--") => {
+                    if !options.params.set_flag(flag, value)? {
+                        anyhow::bail!("unknown flag {}", flag);
+                    }
+                    i += 1;
+                }
+                color_scale => options.color_scale = color_scale.parse()?,
+            }
+            i += 1;
+        }
+        Ok(options)
+    }
+}
+
+fn modified(path: &Option<PathBuf>) -> Option<SystemTime> {
+    path.as_ref()
+        .and_then(|path| std::fs::metadata(path).ok())
+        .and_then(|metadata| metadata.modified().ok())
+}
 
 struct State {
     init: transforms::InitWgpu,
-    particle_bind_groups: Vec<wgpu::BindGroup>,
-    particle_buffers: Vec<wgpu::Buffer>,
-    vertices_buffer: wgpu::Buffer,
-    compute_pipeline: wgpu::ComputePipeline,
-    render_pipeline: wgpu::RenderPipeline,
-    render_bind_group: wgpu::BindGroup,
-    work_group_count: u32,
-    frame_num: usize,
+    sim: boids_sim::Simulation,
+    renderer: boids_render::Renderer,
+    grid: bool,
+    species: boids_sim::SpeciesConfig,
+    seed: u64,
+    /// Where `r` goes back to when it has the current particle count.
+    snapshot: Option<boids_sim::Snapshot>,
+    save_json: bool,
+    recorder: Option<trajectory::Recorder>,
+    /// Where to save the next screenshot, taken once the simulation reaches the given frame.
+    screenshot: Option<(PathBuf, usize)>,
+    /// Whether saving a screenshot ends the run, as it does for `--screenshot`.
+    exit_after_screenshot: bool,
+    exit: bool,
+    /// Set with `--hot-reload`, watching `SHADER_PATHS`.
+    hot_reload: Option<(hot_reload::ShaderWatcher, hot_reload::ErrorCapture)>,
+    params_path: Option<PathBuf>,
+    params_modified: Option<SystemTime>,
+    base_params: boids_sim::SimParams,
+    time_scale: f32,
+    rule_weights: [usize; 3],
+    paused: bool,
+    single_step: bool,
+    cursor: [f32; 2],
 }
 
 impl State {
@@ -43,236 +264,335 @@ impl State {
         }
     }
 
-    async fn new(window: &Window, color_scale: f32) -> Self {
-        let init = transforms::InitWgpu::init_wgpu(window).await;
-
-        let shader = init.device.create_shader_module(&wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("boids.wgsl").into()),
-        });
-
-        let param_data = [
-            0.04f32,
-            0.1,
-            0.025,
-            0.025,
-            0.02,
-            0.05,
-            0.025,
-            0.005,
-        ].to_vec();
-
-        let color_scale_buffer = init.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Color Scale Buffer"),
-            contents: bytemuck::cast_slice(&[color_scale]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let param_buffer = init.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Param Buffer"),
-            contents: bytemuck::cast_slice(&param_data),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let compute_bind_group_layout = init.device.create_bind_group_layout(
-            &wgpu::BindGroupLayoutDescriptor{
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: wgpu::BufferSize::new(
-                            (param_data.len() * mem::size_of::<f32>()) as _,
-                        ),
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage {read_only: true},
-                        has_dynamic_offset: false,
-                        min_binding_size: wgpu::BufferSize::new((NUM_PARTICLES * 16) as _),
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage {read_only: false},
-                        has_dynamic_offset: false,
-                        min_binding_size: wgpu::BufferSize::new((NUM_PARTICLES * 16) as _),
-                    },
-                    count: None,
-                },
-            ],
-            label: None,
-        });
-
-        let compute_pipeline_layout = init.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Compute"),
-            bind_group_layouts: &[&compute_bind_group_layout],
-            push_constant_ranges: &[],
-        });
-
-        let render_bind_group_layout = init.device.create_bind_group_layout(
-            &wgpu::BindGroupLayoutDescriptor{
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: wgpu::BufferSize::new(
-                            ([color_scale].len() * mem::size_of::<f32>()) as _,
-                        ),
-                    },
-                    count: None,
-                },
-            ],
-            label: None,
-        });
+    async fn new(window: &Window, options: &Options) -> anyhow::Result<Self> {
+        let init = transforms::InitWgpu::init_wgpu(window).await?;
+        let renderer = boids_render::Renderer::new(
+            &init.device,
+            init.config.format,
+            boids_render::RenderParams::new(options.color_scale, options.species.colors()),
+        );
+
+        let max = boids_sim::Simulation::max_particles(&init.device.limits(), options.workgroup_size);
+        if options.num_particles > max {
+            anyhow::bail!(
+                "{} particles is more than this device can simulate with workgroup size {}, at most {}",
+                options.num_particles,
+                options.workgroup_size,
+                max
+            );
+        }
+        let particles = match &options.snapshot {
+            Some(snapshot) => snapshot.particles.clone(),
+            None => boids_sim::initial_particles(options.num_particles, options.seed),
+        };
+        let mut sim = boids_sim::Simulation::new(
+            &init.device,
+            &init.queue,
+            options.params,
+            &particles,
+            options.workgroup_size,
+        );
+        if options.grid {
+            sim.enable_grid(&init.device);
+        }
+        sim.set_obstacles(&init.queue, options.obstacles);
+        match &options.snapshot {
+            Some(snapshot) => sim.restore(&init.queue, snapshot)?,
+            None => sim.set_species(&init.queue, options.species.matrix()?, options.species.assign(options.num_particles)),
+        }
+        if let Some(path) = &options.sdf_path {
+            sim.set_sdf(&init.device, &init.queue, Some(boids_sim::Sdf::load(path)?));
+        }
 
-        let render_bind_group = init.device.create_bind_group(&wgpu::BindGroupDescriptor{
-            layout: &render_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: color_scale_buffer.as_entire_binding(),
-                },
-            ],
-            label: Some("Render Bind Group"),
+        let recorder = match &options.trajectory {
+            Some(path) => Some(trajectory::Recorder::new(
+                &init.device,
+                path,
+                options.trajectory_every,
+                sim.num_particles,
+                sim.species_ids.clone(),
+            )?),
+            None => None,
+        };
+
+        let hot_reload = options.hot_reload.then(|| {
+            (hot_reload::ShaderWatcher::with_includes(SHADER_PATHS), hot_reload::ErrorCapture::install(&init.device))
         });
 
-        let render_pipeline_layout = init.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render"),
-            bind_group_layouts: &[&render_bind_group_layout],
-            push_constant_ranges: &[],
-        });
+        let mut state = Self {
+            init,
+            sim,
+            renderer,
+            grid: options.grid,
+            species: options.species.clone(),
+            seed: options.seed,
+            snapshot: options.snapshot.clone(),
+            save_json: options.save_json,
+            recorder,
+            screenshot: options.screenshot.clone().map(|path| (path, options.screenshot_frame)),
+            exit_after_screenshot: options.screenshot.is_some(),
+            exit: false,
+            hot_reload,
+            params_path: options.params_path.clone(),
+            params_modified: modified(&options.params_path),
+            base_params: options.params,
+            time_scale: 1.0,
+            rule_weights: [0; 3],
+            paused: false,
+            single_step: false,
+            cursor: [0.0, 0.0],
+        };
+        state.reload_shaders();
+        Ok(state)
+    }
 
-        let render_pipeline = init.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[
-                    wgpu::VertexBufferLayout {
-                        array_stride: 4 * 4,
-                        step_mode: wgpu::VertexStepMode::Instance,
-                        attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2],
-                    },
-                    wgpu::VertexBufferLayout {
-                        array_stride: 2 * 4,
-                        step_mode: wgpu::VertexStepMode::Vertex,
-                        attributes: &wgpu::vertex_attr_array![2 => Float32x2],
-                    },
-                ]
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[init.config.format.into()]
-            }),
-            primitive: wgpu::PrimitiveState::default(),
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
-        });
+    fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        if new_size.width > 0 && new_size.height > 0 {
+            self.init.size = new_size;
+            self.init.config.width = new_size.width;
+            self.init.config.height = new_size.height;
+            self.init.surface.configure(&self.init.device, &self.init.config);
+        }
+    }
 
-        let compute_pipeline = init.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Compute Pipeline"),
-            layout: Some(&compute_pipeline_layout),
-            module: &shader,
-            entry_point: "cs_main",
-        });
+    /// `base_params` with the interactive time step and rule weight changes applied.
+    fn effective_params(&self) -> boids_sim::SimParams {
+        let mut params = self.base_params;
+        params.delta_t *= self.time_scale;
+        params.rule1_scale *= RULE_WEIGHTS[self.rule_weights[0]];
+        params.rule2_scale *= RULE_WEIGHTS[self.rule_weights[1]];
+        params.rule3_scale *= RULE_WEIGHTS[self.rule_weights[2]];
+        params
+    }
 
-        let vertex_data = [-0.01f32, -0.02, 0.01, -0.02, 0.00, 0.02];
-        let vertices_buffer = init.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::bytes_of(&vertex_data),
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-        });
+    fn apply_params(&mut self) {
+        let params = self.effective_params();
+        self.sim.set_params(&self.init.device, &self.init.queue, params);
+    }
 
-        let mut initial_particle_data = vec![0.0f32; (4 * NUM_PARTICLES) as usize];
-        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
-        let unif = Uniform::new_inclusive(-1.0, 1.0);
-        for particle_instance_chunk in initial_particle_data.chunks_mut(4) {
-            particle_instance_chunk[0] = unif.sample(&mut rng);       // pos x
-            particle_instance_chunk[1] = unif.sample(&mut rng);       // pos y
-            particle_instance_chunk[2] = unif.sample(&mut rng) * 0.1; // vel x
-            particle_instance_chunk[3] = unif.sample(&mut rng) * 0.1; // vel y
+    /// Waits for the trajectory captures still in flight and closes the file.
+    fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            match recorder.finish(&self.init.device) {
+                Ok(()) => println!("trajectory recording stopped"),
+                Err(e) => log::error!("{}", e),
+            }
         }
+    }
 
-        let mut particle_buffers = Vec::<wgpu::Buffer>::new();
-        let mut particle_bind_groups = Vec::<wgpu::BindGroup>::new();
-        for i in 0..2 {
-            particle_buffers.push(
-                init.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some(&format!("Particle Buffer {}", i)),
-                    contents: bytemuck::cast_slice(&initial_particle_data),
-                    usage: wgpu::BufferUsages::VERTEX
-                        | wgpu::BufferUsages::STORAGE
-                        | wgpu::BufferUsages::COPY_DST,
-                }),
-            );
+    /// Replaces the simulation with a freshly seeded one of a different size, keeping the
+    /// current parameters, attractors and obstacles. The size is capped at what the device can
+    /// dispatch and bind with `workgroup_size`.
+    fn rebuild_sim(&mut self, num_particles: u32, workgroup_size: u32) {
+        let num_particles = num_particles.min(boids_sim::Simulation::max_particles(&self.init.device.limits(), workgroup_size));
+        if self.recorder.as_ref().is_some_and(|recorder| recorder.num_particles() != num_particles) {
+            self.stop_recording();
         }
-
-        for i in 0..2 {
-            particle_bind_groups.push(init.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &compute_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: param_buffer.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: particle_buffers[i % 2].as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: particle_buffers[(i + 1) % 2].as_entire_binding(),
-                    },
-                ],
-                label: None,
-            }));
+        let particles = boids_sim::initial_particles(num_particles, self.seed);
+        let mut sim = boids_sim::Simulation::new(
+            &self.init.device,
+            &self.init.queue,
+            self.sim.params,
+            &particles,
+            workgroup_size,
+        );
+        if self.grid {
+            sim.enable_grid(&self.init.device);
+        }
+        sim.set_attractors(&self.init.queue, self.sim.attractors);
+        sim.set_obstacles(&self.init.queue, self.sim.obstacles);
+        sim.set_species(&self.init.queue, self.sim.species, self.species.assign(num_particles));
+        if self.sim.sdf.is_some() {
+            sim.set_sdf(&self.init.device, &self.init.queue, self.sim.sdf.take());
         }
-        let work_group_count = ((NUM_PARTICLES as f32) / (PARTICLES_PER_GROUP as f32)).ceil() as u32;
+        self.sim = sim;
+        self.reload_shaders();
+        println!("{} particles, workgroup size {}", num_particles, workgroup_size);
+    }
 
-        Self {
-            init,
-            particle_bind_groups,
-            particle_buffers,
-            vertices_buffer,
-            compute_pipeline,
-            render_pipeline,
-            render_bind_group,
-            work_group_count,
-            frame_num: 0,
+    /// With `--hot-reload`, rebuilds the render and compute pipelines from the shaders on disk,
+    /// keeping the current ones if either fails to read or compile. True if they were replaced.
+    fn reload_shaders(&mut self) -> bool {
+        let Some((watcher, errors)) = &self.hot_reload else {
+            return false;
+        };
+        let [path, grid_path] = SHADER_PATHS;
+        let preprocessor = watcher.preprocessor();
+        let reloaded = hot_reload::read_source(preprocessor, Path::new(path)).and_then(|source| {
+            let grid_source = hot_reload::read_source(preprocessor, Path::new(grid_path))?;
+            let (render_pipeline, pipelines) = errors.capture(|| {
+                let render_pipeline = self.renderer.build_pipeline(&self.init.device, preprocessor, &source);
+                let pipelines = self.sim.build_pipelines(&self.init.device, preprocessor, &source, &grid_source);
+                (render_pipeline, pipelines)
+            })?;
+            Ok((render_pipeline, pipelines.map_err(|e| e.to_string())?))
+        });
+        match reloaded {
+            Ok((render_pipeline, pipelines)) => {
+                self.renderer.set_pipeline(render_pipeline);
+                self.sim.set_pipelines(pipelines);
+                true
+            }
+            Err(e) => {
+                log::error!("keeping the previous shaders:\n{}", e);
+                false
+            }
         }
     }
 
-    fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        if new_size.width > 0 && new_size.height > 0 {
-            self.init.size = new_size;
-            self.init.config.width = new_size.width;
-            self.init.config.height = new_size.height;
-            self.init.surface.configure(&self.init.device, &self.init.config);
+    fn reset(&mut self) {
+        match &self.snapshot {
+            Some(snapshot) if snapshot.particles.len() == self.sim.num_particles as usize => {
+                if let Err(e) = self.sim.restore(&self.init.queue, snapshot) {
+                    log::error!("{}", e);
+                }
+            }
+            _ => {
+                let particles = boids_sim::initial_particles(self.sim.num_particles, self.seed);
+                self.sim.reset(&self.init.queue, &particles);
+            }
         }
+        println!("reset");
+    }
+
+    fn save_snapshot(&self) -> anyhow::Result<()> {
+        let snapshot = pollster::block_on(self.sim.snapshot(&self.init.device, &self.init.queue, self.seed))?;
+        let extension = if self.save_json { "json" } else { "snap" };
+        let path = format!("boids_{}.{}", snapshot.frame, extension);
+        snapshot.save(&path)?;
+        println!("saved {}", path);
+        Ok(())
+    }
+
+    fn add_attractor(&mut self, strength: f32) {
+        let mut attractors = self.sim.attractors;
+        attractors.push(self.cursor, strength);
+        self.sim.set_attractors(&self.init.queue, attractors);
     }
 
-    #[allow(unused_variables)]
     fn input(&mut self, event: &WindowEvent) -> bool {
-        false
+        match event {
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(key),
+                    ..
+                },
+                ..
+            } => {
+                match key {
+                    VirtualKeyCode::Space => {
+                        self.paused = !self.paused;
+                        println!("{}", if self.paused { "paused" } else { "resumed" });
+                    }
+                    VirtualKeyCode::Period => {
+                        self.paused = true;
+                        self.single_step = true;
+                    }
+                    VirtualKeyCode::R => {
+                        self.reset();
+                    }
+                    VirtualKeyCode::Up => {
+                        self.time_scale *= 1.25;
+                        println!("time scale: {}", self.time_scale);
+                        self.apply_params();
+                    }
+                    VirtualKeyCode::Down => {
+                        self.time_scale /= 1.25;
+                        println!("time scale: {}", self.time_scale);
+                        self.apply_params();
+                    }
+                    VirtualKeyCode::Key1 | VirtualKeyCode::Key2 | VirtualKeyCode::Key3 => {
+                        let rule = match key {
+                            VirtualKeyCode::Key1 => 0,
+                            VirtualKeyCode::Key2 => 1,
+                            _ => 2,
+                        };
+                        self.rule_weights[rule] = (self.rule_weights[rule] + 1) % RULE_WEIGHTS.len();
+                        println!("rule {} weight: {}", rule + 1, RULE_WEIGHTS[self.rule_weights[rule]]);
+                        self.apply_params();
+                    }
+                    VirtualKeyCode::Equals | VirtualKeyCode::Plus | VirtualKeyCode::NumpadAdd => {
+                        let max = boids_sim::Simulation::max_particles(&self.init.device.limits(), self.sim.workgroup_size);
+                        if self.sim.num_particles < max {
+                            self.rebuild_sim(self.sim.num_particles.saturating_mul(2), self.sim.workgroup_size);
+                        } else {
+                            println!("already at the limit of {} particles", max);
+                        }
+                    }
+                    VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => {
+                        self.rebuild_sim((self.sim.num_particles / 2).max(1), self.sim.workgroup_size);
+                    }
+                    VirtualKeyCode::W => {
+                        let next = WORKGROUP_SIZES
+                            .iter()
+                            .position(|&size| size == self.sim.workgroup_size)
+                            .map_or(0, |i| (i + 1) % WORKGROUP_SIZES.len());
+                        self.rebuild_sim(self.sim.num_particles, WORKGROUP_SIZES[next]);
+                    }
+                    VirtualKeyCode::C => {
+                        self.sim.set_attractors(&self.init.queue, boids_sim::Attractors::default());
+                    }
+                    VirtualKeyCode::S => {
+                        if let Err(e) = self.save_snapshot() {
+                            log::error!("{}", e);
+                        }
+                    }
+                    VirtualKeyCode::P => {
+                        self.screenshot = Some((screenshot::next_path("boids"), 0));
+                    }
+                    VirtualKeyCode::B => {
+                        self.base_params.boundary = (self.base_params.boundary + 1) % boids_sim::BOUNDARY_NAMES.len() as u32;
+                        println!("boundary: {}", boids_sim::BOUNDARY_NAMES[self.base_params.boundary as usize]);
+                        self.apply_params();
+                    }
+                    _ => return false,
+                }
+                true
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let size = self.init.size;
+                self.cursor = [
+                    (position.x as f32 / size.width as f32) * 2.0 - 1.0,
+                    1.0 - (position.y as f32 / size.height as f32) * 2.0,
+                ];
+                true
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button,
+                ..
+            } => {
+                match button {
+                    MouseButton::Left => self.add_attractor(ATTRACTOR_STRENGTH),
+                    MouseButton::Right => self.add_attractor(-ATTRACTOR_STRENGTH),
+                    _ => return false,
+                }
+                true
+            }
+            _ => false,
+        }
     }
 
     fn update(&mut self) {
-        // empty
+        if self.hot_reload.as_mut().is_some_and(|(watcher, _)| watcher.changed()) && self.reload_shaders() {
+            println!("reloaded shaders");
+        }
+        let params_modified = modified(&self.params_path);
+        if params_modified == self.params_modified {
+            return;
+        }
+        self.params_modified = params_modified;
+        if let Some(path) = &self.params_path {
+            match boids_sim::SimParams::load(path) {
+                Ok(params) => {
+                    println!("reloaded {}", path.display());
+                    self.base_params = params;
+                    self.apply_params();
+                }
+                Err(e) => log::error!("{}", e),
+            }
+        }
     }
 
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
@@ -285,92 +605,141 @@ impl State {
             label: Some("Render Encoder"),
         });
 
-        {
-            let mut compute_pass = encoder.begin_compute_pass(
-                &wgpu::ComputePassDescriptor { label: None });
-            compute_pass.set_pipeline(&self.compute_pipeline);
-            compute_pass.set_bind_group(0, &self.particle_bind_groups[self.frame_num % 2], &[]);
-            compute_pass.dispatch(self.work_group_count, 1, 1);
+        if !self.paused || self.single_step {
+            self.step(&mut encoder);
+            self.single_step = false;
         }
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.5,
-                            g: 0.5,
-                            b: 0.5,
-                            a: 1.0,
-                        }),
-                        store: true,
-                    }
-                }],
-                depth_stencil_attachment: None,
-            });
-
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_vertex_buffer(0, self.particle_buffers[(self.frame_num + 1) % 2].slice(..));
-            render_pass.set_vertex_buffer(1, self.vertices_buffer.slice(..));
-            render_pass.set_bind_group(0, &self.render_bind_group, &[]);
-            render_pass.draw(0..3, 0..NUM_PARTICLES);
-        }
-        self.frame_num += 1;
+        self.draw(&mut encoder, &view);
+        let capture = match &self.screenshot {
+            Some((_, frame)) if self.sim.frame_num >= *frame => {
+                // the surface texture cannot be copied from, so draw the frame again into one that can
+                let (path, _) = self.screenshot.take().unwrap();
+                let config = &self.init.config;
+                let capture = screenshot::Capture::new(&self.init.device, config.width, config.height, config.format);
+                self.draw(&mut encoder, &capture.view);
+                Some((capture, path))
+            }
+            _ => None,
+        };
 
         self.init.queue.submit(iter::once(encoder.finish()));
         output.present();
 
+        if let Some((capture, path)) = capture {
+            match pollster::block_on(capture.save_png(&self.init.device, &self.init.queue, &path)) {
+                Ok(()) => println!("saved {}", path.display()),
+                Err(e) => log::error!("{}", e),
+            }
+            self.exit = self.exit_after_screenshot;
+        }
+
+        self.submitted();
+
+        Ok(())
+    }
+
+    /// Advances the simulation by one step and captures its trajectory if recording.
+    fn step(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.sim.step(encoder);
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = recorder.capture(&self.init.device, encoder, self.sim.current_buffer(), self.sim.frame_num) {
+                log::error!("{}", e);
+            }
+        }
+    }
+
+    /// Call after submitting an encoder `step` recorded into.
+    fn submitted(&mut self) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.submitted();
+            if let Err(e) = recorder.poll(&self.init.device) {
+                log::error!("{}", e);
+            }
+        }
+    }
+
+    /// Steps and renders `frames` frames into an offscreen texture the size of the window and
+    /// writes them to `path`. Every frame advances the simulation by exactly one step of
+    /// `delta_t`, so the same options always produce the same animation.
+    fn record(&mut self, path: &Path, frames: usize, fps: u32) -> anyhow::Result<()> {
+        let config = &self.init.config;
+        let capture = screenshot::Capture::new(&self.init.device, config.width, config.height, config.format);
+        let mut recording = screenshot::Recording::new(path, fps)?;
+        for _ in 0..frames {
+            let mut encoder = self.init.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Record Encoder"),
+            });
+            self.step(&mut encoder);
+            self.draw(&mut encoder, &capture.view);
+            self.init.queue.submit(iter::once(encoder.finish()));
+            self.submitted();
+            let pixels = pollster::block_on(capture.read_rgba(&self.init.device, &self.init.queue))?;
+            recording.push(pixels, capture.width, capture.height)?;
+        }
+        println!("wrote {} frames to {}", recording.frames(), path.display());
         Ok(())
     }
+
+    fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        self.renderer.draw(encoder, view, &self.sim);
+    }
 }
 
-fn main() {
-    let mut color_scale = "0.1";
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() > 1 {
-        color_scale = &args[1];
+fn main() -> anyhow::Result<()> {
+    let options = Options::from_args()?;
+    if options.check_shaders {
+        if !validate::check_shaders() {
+            anyhow::bail!("shader validation failed");
+        }
+        return Ok(());
     }
-    let clr = color_scale.parse::<f32>();
+    println!("{}", CONTROLS);
 
     env_logger::init();
     let event_loop = EventLoop::new();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
-    window.set_title(&*format!("boids"));
-
-    let mut state = pollster::block_on(State::new(&window, clr.unwrap()));
+    let window = WindowBuilder::new()
+        .with_visible(options.record.is_none())
+        .build(&event_loop)
+        .unwrap();
+    window.set_title("boids");
+
+    let mut state = pollster::block_on(State::new(&window, &options))?;
+
+    if let Some(path) = &options.record {
+        let result = state.record(path, options.record_frames, options.record_fps);
+        state.stop_recording();
+        return result;
+    }
 
     event_loop.run(move |event, _, control_flow| {
         match event {
             Event::WindowEvent {
                 ref event,
                 window_id,
-            } if window_id == window.id() => {
-                if !state.input(event) {
-                    match event {
-                        WindowEvent::CloseRequested | WindowEvent::KeyboardInput {
-                            input: KeyboardInput {
-                                state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Escape),
-                                ..
-                            },
+            } if window_id == window.id() && !state.input(event) => {
+                match event {
+                    WindowEvent::CloseRequested | WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::Escape),
                             ..
-                        } => *control_flow = ControlFlow::Exit,
-                        WindowEvent::Resized(physical_size) => {
-                            state.resize(*physical_size);
-                        }
-                        WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                            state.resize(**new_inner_size);
-                        }
-                        _ => {}
+                        },
+                        ..
+                    } => *control_flow = ControlFlow::Exit,
+                    WindowEvent::Resized(physical_size) => {
+                        state.resize(*physical_size);
+                    }
+                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                        state.resize(**new_inner_size);
                     }
+                    _ => {}
                 }
             }
             Event::RedrawRequested(_) => {
                 state.update();
                 match state.render() {
+                    Ok(_) if state.exit => *control_flow = ControlFlow::Exit,
                     Ok(_) => {}
                     Err(wgpu::SurfaceError::Lost) => state.resize(state.init.size),
                     Err(wgpu::SurfaceError::OutOfMemory) => *control_flow = ControlFlow::Exit,
@@ -380,6 +749,7 @@ fn main() {
             Event::MainEventsCleared => {
                 window.request_redraw();
             }
+            Event::LoopDestroyed => state.stop_recording(),
             _ => {}
         }
     });
@@ -0,0 +1,639 @@
+#![allow(dead_code)]
+use std:: { iter, mem, path::PathBuf, time::Instant };
+use cgmath::{Point3, Vector3};
+use rand::{
+    distributions::{Distribution, Uniform},
+    SeedableRng,
+};
+use wgpu::util::DeviceExt;
+use camera::CameraController;
+use winit::{
+    event::*,
+    window::Window,
+    event_loop::{ControlFlow, EventLoop},
+    window::WindowBuilder,
+};
+
+mod boids_sim;
+#[path="../common/camera.rs"]
+mod camera;
+#[path="../common/compute.rs"]
+mod compute;
+#[path="../common/reflect.rs"]
+mod reflect;
+#[path="../common/screenshot.rs"]
+mod screenshot;
+#[path="../common/transforms.rs"]
+mod transforms;
+#[path="../common/validate.rs"]
+mod validate;
+
+const SEED: u64 = 42;
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+const CONTROLS: &str = "\
+left drag   orbit the camera (--camera orbit)
+wheel       zoom in / out
+w/a/s/d     fly forward / left / back / right (--camera fly)
+e / q       fly up / down
+right drag  look around; wheel changes the flying speed
+space       pause / resume
+r           reset to the seeded initial state
+p           save a screenshot to boids3d_NNN.png";
+
+/// `[pos, vel]` with the w components unused, laid out like `Particle` in boids3d.wgsl.
+type Particle3 = [[f32; 4]; 2];
+
+const PARTICLE3_SIZE: u64 = mem::size_of::<Particle3>() as u64;
+
+/// The particles and the `tetrahedron` mesh, as the `vs_main` input locations each holds, how
+/// it steps and the size of one element on the Rust side.
+const VERTEX_BUFFERS: [(&[u32], wgpu::VertexStepMode, u64); 2] = [
+    (&[0, 1], wgpu::VertexStepMode::Instance, PARTICLE3_SIZE),
+    (&[2, 3], wgpu::VertexStepMode::Vertex, mem::size_of::<[[f32; 3]; 2]>() as u64),
+];
+
+/// Positions uniform in the [-1, 1] box, velocities uniform in [-0.1, 0.1].
+fn initial_particles(num_particles: u32, seed: u64) -> Vec<Particle3> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let unif = Uniform::new_inclusive(-1.0, 1.0);
+    (0..num_particles)
+        .map(|_| {
+            let pos = [unif.sample(&mut rng), unif.sample(&mut rng), unif.sample(&mut rng), 0.0];
+            let vel = [unif.sample(&mut rng) * 0.1, unif.sample(&mut rng) * 0.1, unif.sample(&mut rng) * 0.1, 0.0];
+            [pos, vel]
+        })
+        .collect()
+}
+
+/// A tetrahedron pointing along +z, as `[position, normal]` per vertex with flat face normals.
+fn tetrahedron() -> Vec<[[f32; 3]; 2]> {
+    let tip = Vector3::new(0.0, 0.0, 0.04);
+    let base = [
+        Vector3::new(0.0, 0.015, -0.02),
+        Vector3::new(-0.013, -0.0075, -0.02),
+        Vector3::new(0.013, -0.0075, -0.02),
+    ];
+    let faces = [
+        [tip, base[0], base[1]],
+        [tip, base[1], base[2]],
+        [tip, base[2], base[0]],
+        [base[0], base[2], base[1]],
+    ];
+    faces
+        .iter()
+        .flat_map(|face| {
+            use cgmath::InnerSpace;
+            let normal = (face[1] - face[0]).cross(face[2] - face[0]).normalize();
+            face.iter().map(move |v| [(*v).into(), normal.into()])
+        })
+        .collect()
+}
+
+/// Mirrors `SimParams` in boids3d.wgsl: the rules of `boids_sim::SimParams` without its
+/// boundary modes, since the 3D box always wraps.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SimParams3 {
+    delta_t: f32,
+    rule1_distance: f32,
+    rule2_distance: f32,
+    rule3_distance: f32,
+    rule1_scale: f32,
+    rule2_scale: f32,
+    rule3_scale: f32,
+}
+
+impl Default for SimParams3 {
+    fn default() -> Self {
+        let params = boids_sim::SimParams::default();
+        Self {
+            delta_t: params.delta_t,
+            rule1_distance: params.rule1_distance,
+            rule2_distance: params.rule2_distance,
+            rule3_distance: params.rule3_distance,
+            rule1_scale: params.rule1_scale,
+            rule2_scale: params.rule2_scale,
+            rule3_scale: params.rule3_scale,
+        }
+    }
+}
+
+impl SimParams3 {
+    /// Sets the field named by a command line flag such as `--rule1-distance`.
+    /// Returns `false` if `flag` is not a parameter, which includes the 2D boundary flags.
+    fn set_flag(&mut self, flag: &str, value: &str) -> anyhow::Result<bool> {
+        let field = match flag {
+            "--delta-t" => &mut self.delta_t,
+            "--rule1-distance" => &mut self.rule1_distance,
+            "--rule2-distance" => &mut self.rule2_distance,
+            "--rule3-distance" => &mut self.rule3_distance,
+            "--rule1-scale" => &mut self.rule1_scale,
+            "--rule2-scale" => &mut self.rule2_scale,
+            "--rule3-scale" => &mut self.rule3_scale,
+            _ => return Ok(false),
+        };
+        *field = value
+            .parse()
+            .map_err(|e| anyhow::anyhow!("{} {:?}: {}", flag, value, e))?;
+        Ok(true)
+    }
+}
+
+/// The most particles one dispatch of `workgroup_size` covers whose buffers `limits` can bind,
+/// like `boids_sim::Simulation::max_particles` for the larger 3D particles.
+fn max_particles(limits: &wgpu::Limits, workgroup_size: u32) -> u32 {
+    let per_binding = limits.max_storage_buffer_binding_size as u64 / PARTICLE3_SIZE;
+    compute::MAX_WORKGROUPS_PER_DIMENSION.saturating_mul(workgroup_size).min(per_binding as u32)
+}
+
+/// Mirrors `Camera` in boids3d.wgsl.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CameraUniform {
+    view_project: [[f32; 4]; 4],
+    color_scale: f32,
+    _pad: [f32; 3],
+}
+
+fn create_depth_view(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::TextureView {
+    let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Depth Texture"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: DEPTH_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+    });
+    depth_texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// Usage: boids3d [COLOR_SCALE] [--particles N] [--delta-t X] [--rule1-distance X] ...
+///                [--screenshot FILE.png] [--screenshot-frame N] [--camera orbit|fly]
+///
+/// `--screenshot` saves the frame drawn after step N (1 by default) and exits.
+struct Options {
+    color_scale: f32,
+    num_particles: u32,
+    params: SimParams3,
+    screenshot: Option<PathBuf>,
+    screenshot_frame: usize,
+    fly: bool,
+}
+
+impl Options {
+    fn from_args() -> anyhow::Result<Self> {
+        let mut options = Self {
+            color_scale: 0.1,
+            num_particles: 2000,
+            params: SimParams3::default(),
+            screenshot: None,
+            screenshot_frame: 1,
+            fly: false,
+        };
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        let mut i = 0;
+        while i < args.len() {
+            let value = args.get(i + 1).map(String::as_str).unwrap_or_default();
+            match args[i].as_str() {
+                "--particles" => {
+                    options.num_particles = value.parse()?;
+                    if options.num_particles == 0 {
+                        anyhow::bail!("--particles must be at least 1");
+                    }
+                    i += 1;
+                }
+                "--screenshot" => {
+                    options.screenshot = Some(PathBuf::from(value));
+                    i += 1;
+                }
+                "--screenshot-frame" => {
+                    options.screenshot_frame = value.parse()?;
+                    i += 1;
+                }
+                "--camera" => {
+                    options.fly = match value {
+                        "orbit" => false,
+                        "fly" => true,
+                        _ => anyhow::bail!("--camera is orbit or fly, not {:?}", value),
+                    };
+                    i += 1;
+                }
+                flag if flag.starts_with("--") => {
+                    if !options.params.set_flag(flag, value)? {
+                        anyhow::bail!("unknown flag {}", flag);
+                    }
+                    i += 1;
+                }
+                color_scale => options.color_scale = color_scale.parse()?,
+            }
+            i += 1;
+        }
+        Ok(options)
+    }
+}
+
+struct State {
+    init: transforms::InitWgpu,
+    camera: Box<dyn CameraController>,
+    last_update: Instant,
+    camera_uniform: CameraUniform,
+    camera_buffer: wgpu::Buffer,
+    depth_view: wgpu::TextureView,
+    particle_buffers: Vec<wgpu::Buffer>,
+    particle_bind_groups: Vec<wgpu::BindGroup>,
+    compute_pipeline: wgpu::ComputePipeline,
+    mesh_buffer: wgpu::Buffer,
+    mesh_vertex_count: u32,
+    render_pipeline: wgpu::RenderPipeline,
+    render_bind_group: wgpu::BindGroup,
+    num_particles: u32,
+    work_group_count: u32,
+    frame_num: usize,
+    paused: bool,
+    /// Where to save the next screenshot, taken once the simulation reaches the given frame.
+    screenshot: Option<(PathBuf, usize)>,
+    /// Whether saving a screenshot ends the run, as it does for `--screenshot`.
+    exit_after_screenshot: bool,
+    exit: bool,
+}
+
+impl State {
+    async fn new(window: &Window, options: &Options) -> anyhow::Result<Self> {
+        let init = transforms::InitWgpu::init_wgpu(window).await?;
+        let num_particles = options.num_particles;
+        let workgroup_size = boids_sim::Simulation::DEFAULT_WORKGROUP_SIZE;
+        let max = max_particles(&init.device.limits(), workgroup_size);
+        if num_particles > max {
+            anyhow::bail!("{} particles is more than this device can simulate, at most {}", num_particles, max);
+        }
+
+        let shader = validate::shader_module(&init.device, "boids3d.wgsl", include_str!("boids3d.wgsl"));
+
+        let camera: Box<dyn CameraController> = if options.fly {
+            Box::new(camera::FlyController::new(Point3::new(0.0, 0.5, 3.5), Point3::new(0.0, 0.0, 0.0)))
+        } else {
+            Box::new(camera::OrbitController::new(Point3::new(0.0, 0.0, 0.0), 3.5, 0.8, 0.4))
+        };
+        let camera_uniform = CameraUniform {
+            view_project: camera.view_project(init.config.width as f32 / init.config.height as f32).into(),
+            color_scale: options.color_scale,
+            _pad: [0.0; 3],
+        };
+        let camera_buffer = init.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Camera Buffer"),
+            contents: bytemuck::bytes_of(&camera_uniform),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let render_reflection = reflect::Reflection::new("boids3d.wgsl", include_str!("boids3d.wgsl"), &["vs_main", "fs_main"])?;
+        render_reflection.check_buffer_size(0, 0, mem::size_of::<CameraUniform>() as u64)?;
+        let vertex_attributes = VERTEX_BUFFERS
+            .iter()
+            .map(|(locations, _, element_size)| {
+                let (attributes, stride) = render_reflection.vertex_attributes(locations)?;
+                if stride != *element_size {
+                    anyhow::bail!("boids3d.wgsl: the inputs at {:?} do not match their buffer", locations);
+                }
+                Ok((attributes, stride))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let vertex_buffers = VERTEX_BUFFERS
+            .iter()
+            .zip(&vertex_attributes)
+            .map(|((_, step_mode, _), (attributes, array_stride))| wgpu::VertexBufferLayout {
+                array_stride: *array_stride,
+                step_mode: *step_mode,
+                attributes,
+            })
+            .collect::<Vec<_>>();
+        let render_bind_group_layouts = render_reflection.bind_group_layouts(&init.device)?;
+
+        let render_bind_group = init.device.create_bind_group(&wgpu::BindGroupDescriptor{
+            layout: &render_bind_group_layouts[0],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("Render Bind Group"),
+        });
+
+        let render_pipeline_layout = render_reflection.pipeline_layout(&init.device, &render_bind_group_layouts);
+
+        let render_pipeline = init.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &vertex_buffers,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[init.config.format.into()]
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: camera.projection().depth_compare(),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+        });
+
+        let mesh = tetrahedron();
+        let mesh_buffer = init.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Vertex Buffer"),
+            contents: bytemuck::cast_slice(&mesh),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let compute_shader = boids_sim::compute_shader(&init.device, &validate::preprocess::Preprocessor::new(), "boids3d.wgsl", include_str!("boids3d.wgsl"), workgroup_size)?;
+        let param_buffer = init.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Param Buffer"),
+            contents: bytemuck::bytes_of(&options.params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let compute_reflection = reflect::Reflection::new("boids3d.wgsl", include_str!("boids3d.wgsl"), &["cs_main"])?;
+        compute_reflection.check_buffer_size(0, 0, mem::size_of::<SimParams3>() as u64)?;
+        compute_reflection.check_buffer_size(0, 1, num_particles as u64 * PARTICLE3_SIZE)?;
+        compute_reflection.check_buffer_size(0, 2, num_particles as u64 * PARTICLE3_SIZE)?;
+        let compute_bind_group_layouts = compute_reflection.bind_group_layouts(&init.device)?;
+        let compute_pipeline_layout = compute_reflection.pipeline_layout(&init.device, &compute_bind_group_layouts);
+
+        let compute_pipeline = init.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Compute Pipeline"),
+            layout: Some(&compute_pipeline_layout),
+            module: &compute_shader,
+            entry_point: "cs_main",
+        });
+
+        let particles = initial_particles(num_particles, SEED);
+        let mut particle_buffers = Vec::<wgpu::Buffer>::new();
+        let mut particle_bind_groups = Vec::<wgpu::BindGroup>::new();
+        for i in 0..2 {
+            particle_buffers.push(
+                init.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("Particle Buffer {}", i)),
+                    contents: bytemuck::cast_slice(&particles),
+                    usage: wgpu::BufferUsages::VERTEX
+                        | wgpu::BufferUsages::STORAGE
+                        | wgpu::BufferUsages::COPY_DST,
+                }),
+            );
+        }
+
+        for i in 0..2 {
+            particle_bind_groups.push(init.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &compute_bind_group_layouts[0],
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: param_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: particle_buffers[i % 2].as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: particle_buffers[(i + 1) % 2].as_entire_binding(),
+                    },
+                ],
+                label: None,
+            }));
+        }
+
+        let depth_view = create_depth_view(&init.device, &init.config);
+
+        Ok(Self {
+            init,
+            camera,
+            last_update: Instant::now(),
+            camera_uniform,
+            camera_buffer,
+            depth_view,
+            particle_buffers,
+            particle_bind_groups,
+            compute_pipeline,
+            mesh_buffer,
+            mesh_vertex_count: mesh.len() as u32,
+            render_pipeline,
+            render_bind_group,
+            num_particles,
+            work_group_count: num_particles.div_ceil(workgroup_size),
+            frame_num: 0,
+            paused: false,
+            screenshot: options.screenshot.clone().map(|path| (path, options.screenshot_frame)),
+            exit_after_screenshot: options.screenshot.is_some(),
+            exit: false,
+        })
+    }
+
+    fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        if new_size.width > 0 && new_size.height > 0 {
+            self.init.size = new_size;
+            self.init.config.width = new_size.width;
+            self.init.config.height = new_size.height;
+            self.init.surface.configure(&self.init.device, &self.init.config);
+            self.depth_view = create_depth_view(&self.init.device, &self.init.config);
+        }
+    }
+
+    fn input(&mut self, event: &WindowEvent) -> bool {
+        if self.camera.window_event(event) {
+            return true;
+        }
+        match event {
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(key),
+                    ..
+                },
+                ..
+            } => {
+                match key {
+                    VirtualKeyCode::Space => {
+                        self.paused = !self.paused;
+                        println!("{}", if self.paused { "paused" } else { "resumed" });
+                    }
+                    VirtualKeyCode::R => {
+                        let particles = initial_particles(self.num_particles, SEED);
+                        for buffer in self.particle_buffers.iter() {
+                            self.init.queue.write_buffer(buffer, 0, bytemuck::cast_slice(&particles));
+                        }
+                        self.frame_num = 0;
+                        println!("reset");
+                    }
+                    VirtualKeyCode::P => {
+                        self.screenshot = Some((screenshot::next_path("boids3d"), 0));
+                    }
+                    _ => return false,
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn update(&mut self) {
+        let now = Instant::now();
+        self.camera.update((now - self.last_update).as_secs_f32());
+        self.last_update = now;
+        let aspect = self.init.config.width as f32 / self.init.config.height as f32;
+        self.camera_uniform.view_project = self.camera.view_project(aspect).into();
+        self.init.queue.write_buffer(&self.camera_buffer, 0, bytemuck::bytes_of(&self.camera_uniform));
+    }
+
+    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        let output = self.init.surface.get_current_texture()?;
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = self.init.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render Encoder"),
+        });
+
+        if !self.paused {
+            let mut compute_pass = encoder.begin_compute_pass(
+                &wgpu::ComputePassDescriptor { label: None });
+            compute_pass.set_pipeline(&self.compute_pipeline);
+            compute_pass.set_bind_group(0, &self.particle_bind_groups[self.frame_num % 2], &[]);
+            compute_pass.dispatch(self.work_group_count, 1, 1);
+            drop(compute_pass);
+            self.frame_num += 1;
+        }
+
+        self.draw(&mut encoder, &view);
+        let capture = match &self.screenshot {
+            Some((_, frame)) if self.frame_num >= *frame => {
+                // the surface texture cannot be copied from, so draw the frame again into one that
+                // can; it has the surface's size, so the depth buffer is reused
+                let (path, _) = self.screenshot.take().unwrap();
+                let config = &self.init.config;
+                let capture = screenshot::Capture::new(&self.init.device, config.width, config.height, config.format);
+                self.draw(&mut encoder, &capture.view);
+                Some((capture, path))
+            }
+            _ => None,
+        };
+
+        self.init.queue.submit(iter::once(encoder.finish()));
+        output.present();
+
+        if let Some((capture, path)) = capture {
+            match pollster::block_on(capture.save_png(&self.init.device, &self.init.queue, &path)) {
+                Ok(()) => println!("saved {}", path.display()),
+                Err(e) => log::error!("{}", e),
+            }
+            self.exit = self.exit_after_screenshot;
+        }
+
+        Ok(())
+    }
+
+    fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 0.5,
+                        g: 0.5,
+                        b: 0.5,
+                        a: 1.0,
+                    }),
+                    store: true,
+                }
+            }],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.camera.projection().far_depth()),
+                    store: false,
+                }),
+                stencil_ops: None,
+            }),
+        });
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_vertex_buffer(0, self.particle_buffers[self.frame_num % 2].slice(..));
+        render_pass.set_vertex_buffer(1, self.mesh_buffer.slice(..));
+        render_pass.set_bind_group(0, &self.render_bind_group, &[]);
+        render_pass.draw(0..self.mesh_vertex_count, 0..self.num_particles);
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let options = Options::from_args()?;
+    println!("{}", CONTROLS);
+
+    env_logger::init();
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new().build(&event_loop).unwrap();
+    window.set_title("boids3d");
+
+    let mut state = pollster::block_on(State::new(&window, &options))?;
+
+    event_loop.run(move |event, _, control_flow| {
+        match event {
+            Event::WindowEvent {
+                ref event,
+                window_id,
+            } if window_id == window.id() && !state.input(event) => {
+                match event {
+                    WindowEvent::CloseRequested | WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::Escape),
+                            ..
+                        },
+                        ..
+                    } => *control_flow = ControlFlow::Exit,
+                    WindowEvent::Resized(physical_size) => {
+                        state.resize(*physical_size);
+                    }
+                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                        state.resize(**new_inner_size);
+                    }
+                    _ => {}
+                }
+            }
+            Event::DeviceEvent { ref event, .. } => {
+                state.camera.device_event(event);
+            }
+            Event::RedrawRequested(_) => {
+                state.update();
+                match state.render() {
+                    Ok(_) if state.exit => *control_flow = ControlFlow::Exit,
+                    Ok(_) => {}
+                    Err(wgpu::SurfaceError::Lost) => state.resize(state.init.size),
+                    Err(wgpu::SurfaceError::OutOfMemory) => *control_flow = ControlFlow::Exit,
+                    Err(e) => eprintln!("{:?}", e),
+                }
+            }
+            Event::MainEventsCleared => {
+                window.request_redraw();
+            }
+            _ => {}
+        }
+    });
+}
@@ -0,0 +1,142 @@
+#include "common/math.wgsl"
+#define WORKGROUP_SIZE 64
+
+[[block]] struct Camera {
+  viewProject : mat4x4<f32>;
+  colorScale : f32;
+};
+
+[[binding(0), group(0)]] var<uniform> camera : Camera;
+
+struct Input {
+  [[location(0)]] a_particle_pos : vec4<f32>;
+  [[location(1)]] a_particle_vel : vec4<f32>;
+  [[location(2)]] a_pos : vec3<f32>;
+  [[location(3)]] a_normal : vec3<f32>;
+};
+
+struct Output {
+  [[builtin(position)]] position : vec4<f32>;
+  [[location(0)]] v_vel : vec3<f32>;
+  [[location(1)]] v_normal : vec3<f32>;
+};
+
+// the mesh points along +z; turn it to face along the velocity
+[[stage(vertex)]]
+fn vs_main(input: Input) -> Output {
+  var output : Output;
+  let forward : vec3<f32> = normalize(input.a_particle_vel.xyz);
+  var up : vec3<f32> = vec3<f32>(0.0, 1.0, 0.0);
+  if (abs(forward.y) > 0.99) {
+    up = vec3<f32>(1.0, 0.0, 0.0);
+  }
+  let right : vec3<f32> = normalize(cross(up, forward));
+  let up2 : vec3<f32> = cross(forward, right);
+  let pos : vec3<f32> = right * input.a_pos.x + up2 * input.a_pos.y + forward * input.a_pos.z;
+  output.position = camera.viewProject * vec4<f32>(pos + input.a_particle_pos.xyz, 1.0);
+  output.v_vel = input.a_particle_vel.xyz;
+  output.v_normal = right * input.a_normal.x + up2 * input.a_normal.y + forward * input.a_normal.z;
+  return output;
+}
+
+[[stage(fragment)]]
+fn fs_main(input: Output) -> [[location(0)]] vec4<f32> {
+  let c : f32 = camera.colorScale;
+  let light : vec3<f32> = normalize(vec3<f32>(0.4, 1.0, 0.6));
+  let shade : f32 = 0.35 + 0.65 * max(dot(normalize(input.v_normal), light), 0.0);
+  let v : vec3<f32> = input.v_vel;
+  return vec4<f32>(shade * (c + (1.0-c) * sin(2.0 * PI * v.x)),
+                   shade * (c + (1.0-c) * sin(2.0 * PI * v.y)),
+                   shade * (c + (1.0-c) * cos(PI * (v.x - v.z))),
+                   1.0);
+}
+
+struct Particle {
+  pos : vec4<f32>;
+  vel : vec4<f32>;
+};
+
+[[block]] struct SimParams {
+  deltaT : f32;
+  rule1Distance : f32;
+  rule2Distance : f32;
+  rule3Distance : f32;
+  rule1Scale : f32;
+  rule2Scale : f32;
+  rule3Scale : f32;
+};
+
+[[block]] struct Particles {
+  particles : [[stride(32)]] array<Particle>;
+};
+
+[[binding(0), group(0)]] var<uniform> params : SimParams;
+[[binding(1), group(0)]] var<storage, read> particlesA : Particles;
+[[binding(2), group(0)]] var<storage, read_write> particlesB : Particles;
+
+// cs_main from boids.wgsl in a [-1, 1]^3 box that wraps on every face
+[[stage(compute), workgroup_size(WORKGROUP_SIZE)]]
+fn cs_main([[builtin(global_invocation_id)]] GlobalInvocationID : vec3<u32>) {
+  let total = arrayLength(&particlesA.particles);
+  var index : u32 = GlobalInvocationID.x;
+  if (index >= total) {
+    return;
+  }
+  var vPos : vec3<f32> = particlesA.particles[index].pos.xyz;
+  var vVel : vec3<f32> = particlesA.particles[index].vel.xyz;
+  var cMass : vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+  var cVel : vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+  var colVel : vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+  var cMassCount : u32 = 0u;
+  var cVelCount : u32 = 0u;
+  var pos : vec3<f32>;
+  var vel : vec3<f32>;
+
+  for (var i: u32 = 0u; i < total; i = i + 1u) {
+    if (i == index) {
+      continue;
+    }
+    pos = particlesA.particles[i].pos.xyz;
+    vel = particlesA.particles[i].vel.xyz;
+    if (distance(pos, vPos) < params.rule1Distance) {
+      cMass = cMass + pos;
+      cMassCount = cMassCount + 1u;
+    }
+    if (distance(pos, vPos) < params.rule2Distance) {
+      colVel = colVel - (pos - vPos);
+    }
+    if (distance(pos, vPos) < params.rule3Distance) {
+      cVel = cVel + vel;
+      cVelCount = cVelCount + 1u;
+    }
+  }
+  if (cMassCount > 0u) {
+    cMass = (cMass / f32(cMassCount)) - vPos;
+  }
+  if (cVelCount > 0u) {
+    cVel = cVel / f32(cVelCount);
+  }
+  vVel = vVel + (cMass * params.rule1Scale) + (colVel * params.rule2Scale) + (cVel * params.rule3Scale);
+  vVel = normalize(vVel * params.deltaT);
+  vPos = vPos + (vVel * params.deltaT);
+  if (vPos.x < -1.0) {
+    vPos.x = 1.0;
+  }
+  if (vPos.x > 1.0) {
+    vPos.x = -1.0;
+  }
+  if (vPos.y < -1.0) {
+    vPos.y = 1.0;
+  }
+  if (vPos.y > 1.0) {
+    vPos.y = -1.0;
+  }
+  if (vPos.z < -1.0) {
+    vPos.z = 1.0;
+  }
+  if (vPos.z > 1.0) {
+    vPos.z = -1.0;
+  }
+  particlesB.particles[index].pos = vec4<f32>(vPos, 0.0);
+  particlesB.particles[index].vel = vec4<f32>(vVel, 0.0);
+}
@@ -0,0 +1,155 @@
+// Binned variant of cs_main in boids.wgsl: particles are counted into a uniform grid over
+// [-1, 1]^2, the counts are scanned into cell offsets, particle indices are scattered into
+// cell order and the rules only look at particles in the 3x3 cells around each boid.
+// boids_sim::compute_shader defines WORKGROUP_SIZE as the configured size; grid_scan always
+// runs as a single workgroup of 256.
+
+#define WORKGROUP_SIZE 64
+
+#include "13/boids_shared.wgsl"
+
+[[block]] struct GridParams {
+  dim : u32;
+  numCells : u32;
+  cellSize : f32;
+};
+
+// numCells + 1 entries: per-cell counts, scanned in place into start offsets
+[[block]] struct Cells {
+  offsets : [[stride(4)]] array<atomic<u32>>;
+};
+
+// per particle: (cell, slot within the cell)
+[[block]] struct Keys {
+  keys : [[stride(8)]] array<vec2<u32>>;
+};
+
+[[block]] struct Indices {
+  indices : [[stride(4)]] array<u32>;
+};
+
+[[binding(1), group(0)]] var<storage, read> particlesA : Particles;
+[[binding(2), group(0)]] var<storage, read_write> particlesB : Particles;
+[[binding(3), group(0)]] var<uniform> grid : GridParams;
+[[binding(4), group(0)]] var<storage, read_write> cells : Cells;
+[[binding(5), group(0)]] var<storage, read_write> keys : Keys;
+[[binding(6), group(0)]] var<storage, read_write> sorted : Indices;
+[[binding(7), group(0)]] var<uniform> attractors : Attractors;
+
+fn cell_coords(pos : vec2<f32>) -> vec2<i32> {
+  let last : i32 = i32(grid.dim) - 1;
+  let c : vec2<f32> = floor((pos + vec2<f32>(1.0, 1.0)) / grid.cellSize);
+  return clamp(vec2<i32>(i32(c.x), i32(c.y)), vec2<i32>(0, 0), vec2<i32>(last, last));
+}
+
+[[stage(compute), workgroup_size(WORKGROUP_SIZE)]]
+fn grid_clear([[builtin(global_invocation_id)]] GlobalInvocationID : vec3<u32>) {
+  var index : u32 = GlobalInvocationID.x;
+  if (index > grid.numCells) {
+    return;
+  }
+  atomicStore(&cells.offsets[index], 0u);
+}
+
+[[stage(compute), workgroup_size(WORKGROUP_SIZE)]]
+fn grid_count([[builtin(global_invocation_id)]] GlobalInvocationID : vec3<u32>) {
+  var index : u32 = GlobalInvocationID.x;
+  if (index >= arrayLength(&particlesA.particles)) {
+    return;
+  }
+  let c : vec2<i32> = cell_coords(particlesA.particles[index].pos);
+  let cell : u32 = u32(c.y) * grid.dim + u32(c.x);
+  keys.keys[index] = vec2<u32>(cell, atomicAdd(&cells.offsets[cell], 1u));
+}
+
+var<workgroup> partial : array<u32, 256>;
+
+// Exclusive scan of the cell counts, run as a single workgroup; entry numCells ends up as the total.
+[[stage(compute), workgroup_size(256)]]
+fn grid_scan([[builtin(local_invocation_id)]] LocalInvocationID : vec3<u32>) {
+  let t : u32 = LocalInvocationID.x;
+  let n : u32 = grid.numCells;
+  let chunk : u32 = (n + 255u) / 256u;
+  let begin : u32 = min(t * chunk, n);
+  let end : u32 = min(begin + chunk, n);
+
+  var sum : u32 = 0u;
+  for (var i : u32 = begin; i < end; i = i + 1u) {
+    sum = sum + atomicLoad(&cells.offsets[i]);
+  }
+  partial[t] = sum;
+  workgroupBarrier();
+
+  for (var offset : u32 = 1u; offset < 256u; offset = offset * 2u) {
+    var v : u32 = 0u;
+    if (t >= offset) {
+      v = partial[t - offset];
+    }
+    workgroupBarrier();
+    partial[t] = partial[t] + v;
+    workgroupBarrier();
+  }
+
+  var running : u32 = partial[t] - sum;
+  for (var i : u32 = begin; i < end; i = i + 1u) {
+    let count : u32 = atomicLoad(&cells.offsets[i]);
+    atomicStore(&cells.offsets[i], running);
+    running = running + count;
+  }
+  if (t == 255u) {
+    atomicStore(&cells.offsets[n], partial[255]);
+  }
+}
+
+[[stage(compute), workgroup_size(WORKGROUP_SIZE)]]
+fn grid_scatter([[builtin(global_invocation_id)]] GlobalInvocationID : vec3<u32>) {
+  var index : u32 = GlobalInvocationID.x;
+  if (index >= arrayLength(&particlesA.particles)) {
+    return;
+  }
+  let key : vec2<u32> = keys.keys[index];
+  sorted.indices[atomicLoad(&cells.offsets[key.x]) + key.y] = index;
+}
+
+[[stage(compute), workgroup_size(WORKGROUP_SIZE)]]
+fn cs_grid_main([[builtin(global_invocation_id)]] GlobalInvocationID : vec3<u32>) {
+  let total = arrayLength(&particlesA.particles);
+  var index : u32 = GlobalInvocationID.x;
+  if (index >= total) {
+    return;
+  }
+  let vPos : vec2<f32> = particlesA.particles[index].pos;
+  var vVel : vec2<f32> = particlesA.particles[index].vel;
+  var neighbors : Neighbors = no_neighbors();
+  let vSpecies : u32 = speciesIds.ids[index];
+
+  let home : vec2<i32> = cell_coords(vPos);
+  let dim : i32 = i32(grid.dim);
+  for (var dy : i32 = -1; dy <= 1; dy = dy + 1) {
+    for (var dx : i32 = -1; dx <= 1; dx = dx + 1) {
+      let cx : i32 = home.x + dx;
+      let cy : i32 = home.y + dy;
+      if (cx < 0 || cy < 0 || cx >= dim || cy >= dim) {
+        continue;
+      }
+      let cell : u32 = u32(cy * dim + cx);
+      let first : u32 = atomicLoad(&cells.offsets[cell]);
+      let last : u32 = atomicLoad(&cells.offsets[cell + 1u]);
+      for (var j : u32 = first; j < last; j = j + 1u) {
+        let i : u32 = sorted.indices[j];
+        if (i == index) {
+          continue;
+        }
+        let other : Particle = particlesA.particles[i];
+        neighbors = accumulate_neighbor(neighbors, vPos, vSpecies, other.pos, other.vel, speciesIds.ids[i]);
+      }
+    }
+  }
+  vVel = vVel + neighbor_steering(neighbors, vPos);
+  for (var a : u32 = 0u; a < attractors.count; a = a + 1u) {
+    let item : vec4<f32> = attractors.items[a];
+    let d : vec2<f32> = item.xy - vPos;
+    vVel = vVel + d * (item.z / max(dot(d, d), 0.0025));
+  }
+  particlesB.particles[index] = integrate(vPos, vVel);
+}
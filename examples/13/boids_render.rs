@@ -0,0 +1,184 @@
+#![allow(dead_code)]
+use std::mem;
+use wgpu::util::DeviceExt;
+use crate::boids_sim::{Simulation, PARTICLE_SIZE};
+use crate::reflect::Reflection;
+use crate::validate::{self, preprocess::Preprocessor};
+
+/// Mirrors `Uniforms` in boids.wgsl.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct RenderParams {
+    pub color_scale: f32,
+    pub _pad: [f32; 3],
+    pub species_colors: [[f32; 4]; 4],
+}
+
+impl RenderParams {
+    pub fn new(color_scale: f32, species_colors: [[f32; 4]; 4]) -> Self {
+        Self {
+            color_scale,
+            _pad: [0.0; 3],
+            species_colors,
+        }
+    }
+}
+
+/// Draws a `Simulation`'s particles as triangles pointing along their velocity.
+pub struct Renderer {
+    vertices_buffer: wgpu::Buffer,
+    /// Attributes and array stride of each vertex buffer in `VERTEX_BUFFERS`.
+    vertex_attributes: Vec<(Vec<wgpu::VertexAttribute>, wgpu::BufferAddress)>,
+    format: wgpu::TextureFormat,
+    render_pipeline_layout: wgpu::PipelineLayout,
+    render_pipeline: wgpu::RenderPipeline,
+    render_bind_group: wgpu::BindGroup,
+}
+
+impl Renderer {
+    /// The triangle drawn for each particle, pointing along +y, in clip space.
+    pub const VERTICES: [f32; 6] = [-0.01, -0.02, 0.01, -0.02, 0.00, 0.02];
+    /// The particles, `VERTICES` and species ids, as the `vs_main` input locations each holds,
+    /// how it steps and the size of one element on the Rust side.
+    const VERTEX_BUFFERS: [(&'static [u32], wgpu::VertexStepMode, u64); 3] = [
+        (&[0, 1], wgpu::VertexStepMode::Instance, PARTICLE_SIZE),
+        (&[2], wgpu::VertexStepMode::Vertex, 2 * 4),
+        (&[3], wgpu::VertexStepMode::Instance, 4),
+    ];
+
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, render_params: RenderParams) -> Self {
+        let render_param_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Render Param Buffer"),
+            contents: bytemuck::bytes_of(&render_params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let reflection = Reflection::new("boids.wgsl", include_str!("boids.wgsl"), &["vs_main", "fs_main"])
+            .unwrap_or_else(|e| panic!("{:#}", e));
+        reflection
+            .check_buffer_size(0, 0, mem::size_of::<RenderParams>() as u64)
+            .unwrap_or_else(|e| panic!("{:#}", e));
+        let vertex_attributes = Self::VERTEX_BUFFERS
+            .iter()
+            .map(|(locations, _, element_size)| {
+                let (attributes, stride) = reflection.vertex_attributes(locations).unwrap_or_else(|e| panic!("{:#}", e));
+                assert_eq!(stride, *element_size, "boids.wgsl: the inputs at {:?} do not match their buffer", locations);
+                (attributes, stride)
+            })
+            .collect::<Vec<_>>();
+        let bind_group_layouts = reflection.bind_group_layouts(device).unwrap_or_else(|e| panic!("{:#}", e));
+        let render_bind_group_layout = &bind_group_layouts[0];
+
+        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor{
+            layout: render_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: render_param_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("Render Bind Group"),
+        });
+
+        let render_pipeline_layout = reflection.pipeline_layout(device, &bind_group_layouts);
+
+        let render_pipeline = Self::create_pipeline(
+            device,
+            &Preprocessor::new(),
+            &render_pipeline_layout,
+            &vertex_attributes,
+            include_str!("boids.wgsl"),
+            format,
+        );
+
+        let vertices_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Vertex Buffer"),
+            contents: bytemuck::bytes_of(&Self::VERTICES),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self {
+            vertices_buffer,
+            vertex_attributes,
+            format,
+            render_pipeline_layout,
+            render_pipeline,
+            render_bind_group,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        preprocessor: &Preprocessor,
+        render_pipeline_layout: &wgpu::PipelineLayout,
+        vertex_attributes: &[(Vec<wgpu::VertexAttribute>, wgpu::BufferAddress)],
+        source: &str,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        let shader = validate::shader_module_with(device, preprocessor, "boids.wgsl", source);
+        let buffers = Self::VERTEX_BUFFERS
+            .iter()
+            .zip(vertex_attributes)
+            .map(|((_, step_mode, _), (attributes, array_stride))| wgpu::VertexBufferLayout {
+                array_stride: *array_stride,
+                step_mode: *step_mode,
+                attributes,
+            })
+            .collect::<Vec<_>>();
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &buffers,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[format.into()]
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+        })
+    }
+
+    /// Compiles an edited boids.wgsl source; swap it in with `set_pipeline`.
+    pub fn build_pipeline(&self, device: &wgpu::Device, preprocessor: &Preprocessor, source: &str) -> wgpu::RenderPipeline {
+        Self::create_pipeline(device, preprocessor, &self.render_pipeline_layout, &self.vertex_attributes, source, self.format)
+    }
+
+    pub fn set_pipeline(&mut self, render_pipeline: wgpu::RenderPipeline) {
+        self.render_pipeline = render_pipeline;
+    }
+
+    /// Clears `view` to gray and draws the particles the last step wrote.
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, sim: &Simulation) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 0.5,
+                        g: 0.5,
+                        b: 0.5,
+                        a: 1.0,
+                    }),
+                    store: true,
+                }
+            }],
+            depth_stencil_attachment: None,
+        });
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_vertex_buffer(0, sim.current_buffer().slice(..));
+        render_pass.set_vertex_buffer(1, self.vertices_buffer.slice(..));
+        render_pass.set_vertex_buffer(2, sim.species_id_buffer.slice(..));
+        render_pass.set_bind_group(0, &self.render_bind_group, &[]);
+        render_pass.draw(0..3, 0..sim.num_particles);
+    }
+}
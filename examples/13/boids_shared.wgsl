@@ -0,0 +1,232 @@
+// What cs_main in boids.wgsl and the grid entry points in boids_grid.wgsl share: the particle
+// and parameter types, the obstacle and species bindings, the boundary rules and the
+// integration step. boids_sim.rs mirrors the rules on the CPU in cpu_step.
+
+struct Particle {
+  pos : vec2<f32>;
+  vel : vec2<f32>;
+};
+
+[[block]] struct SimParams {
+  deltaT : f32;
+  rule1Distance : f32;
+  rule2Distance : f32;
+  rule3Distance : f32;
+  rule1Scale : f32;
+  rule2Scale : f32;
+  rule3Scale : f32;
+  // 0: wrap around, 1: reflect off the walls, 2: steer away from the walls, then reflect
+  boundary : u32;
+  boundaryMargin : f32;
+  boundaryScale : f32;
+};
+
+[[block]] struct Particles {
+  particles : [[stride(16)]] array<Particle>;
+};
+
+// xy: position, z: strength, positive attracts and negative repels
+[[block]] struct Attractors {
+  count : u32;
+  items : [[stride(16)]] array<vec4<f32>, 8>;
+};
+
+[[binding(0), group(0)]] var<uniform> params : SimParams;
+
+// shape.xy: center, kind 0: circle of radius shape.z, kind 1: box with half extents shape.zw
+struct Obstacle {
+  shape : vec4<f32>;
+  kind : u32;
+};
+
+// sdf is non-zero when sdfTexture holds a signed distance field over [-1, 1]^2
+[[block]] struct Obstacles {
+  count : u32;
+  sdf : u32;
+  items : [[stride(32)]] array<Obstacle, 16>;
+};
+
+[[binding(0), group(1)]] var<uniform> obstacles : Obstacles;
+[[binding(1), group(1)]] var sdfTexture : texture_2d<f32>;
+
+// weights[a * 4 + b]: how species a reacts to species b, as
+// (cohesion, separation, alignment, flee)
+[[block]] struct Species {
+  count : u32;
+  weights : [[stride(16)]] array<vec4<f32>, 16>;
+};
+
+[[block]] struct SpeciesIds {
+  ids : [[stride(4)]] array<u32>;
+};
+
+[[binding(0), group(2)]] var<uniform> species : Species;
+[[binding(1), group(2)]] var<storage, read> speciesIds : SpeciesIds;
+
+fn sdf_texel(texel : vec2<i32>) -> f32 {
+  let last : vec2<i32> = textureDimensions(sdfTexture) - vec2<i32>(1, 1);
+  return textureLoad(sdfTexture, clamp(texel, vec2<i32>(0, 0), last), 0).x;
+}
+
+// Signed distance to the closest obstacle in x, the direction away from it in yz.
+fn obstacle_distance(p : vec2<f32>) -> vec3<f32> {
+  var closest : vec3<f32> = vec3<f32>(1000.0, 0.0, 0.0);
+  for (var o : u32 = 0u; o < obstacles.count; o = o + 1u) {
+    let item : Obstacle = obstacles.items[o];
+    let rel : vec2<f32> = p - item.shape.xy;
+    var d : f32;
+    var n : vec2<f32>;
+    if (item.kind == 0u) {
+      let len : f32 = length(rel);
+      d = len - item.shape.z;
+      n = rel / max(len, 0.0001);
+    } else {
+      let q : vec2<f32> = abs(rel) - item.shape.zw;
+      let outside : vec2<f32> = max(q, vec2<f32>(0.0, 0.0));
+      let len : f32 = length(outside);
+      d = len + min(max(q.x, q.y), 0.0);
+      if (len > 0.0) {
+        n = sign(rel) * outside / len;
+      } elseif (q.x > q.y) {
+        n = vec2<f32>(sign(rel.x), 0.0);
+      } else {
+        n = vec2<f32>(0.0, sign(rel.y));
+      }
+    }
+    if (d < closest.x) {
+      closest = vec3<f32>(d, n);
+    }
+  }
+  if (obstacles.sdf != 0u) {
+    // texel rows run from y = 1 at the top to y = -1 at the bottom
+    let size : vec2<i32> = textureDimensions(sdfTexture);
+    let uv : vec2<f32> = vec2<f32>(p.x * 0.5 + 0.5, 0.5 - p.y * 0.5) * vec2<f32>(f32(size.x), f32(size.y));
+    let texel : vec2<i32> = vec2<i32>(i32(floor(uv.x)), i32(floor(uv.y)));
+    let d : f32 = sdf_texel(texel);
+    let g : vec2<f32> = vec2<f32>(
+      sdf_texel(texel + vec2<i32>(1, 0)) - sdf_texel(texel - vec2<i32>(1, 0)),
+      sdf_texel(texel - vec2<i32>(0, 1)) - sdf_texel(texel + vec2<i32>(0, 1)));
+    if (d < closest.x && dot(g, g) > 0.0) {
+      closest = vec3<f32>(d, normalize(g));
+    }
+  }
+  return closest;
+}
+
+// Velocity change pushing away from obstacles within the margin and, in steer mode, from the walls.
+fn boundary_steering(p : vec2<f32>) -> vec2<f32> {
+  var steer : vec2<f32> = vec2<f32>(0.0, 0.0);
+  let margin : f32 = params.boundaryMargin;
+  let obstacle : vec3<f32> = obstacle_distance(p);
+  if (obstacle.x < margin) {
+    steer = steer + obstacle.yz * (params.boundaryScale * (1.0 - obstacle.x / margin));
+  }
+  if (params.boundary == 2u) {
+    let near : vec2<f32> = max(p - vec2<f32>(1.0 - margin, 1.0 - margin), vec2<f32>(0.0, 0.0));
+    let far : vec2<f32> = max(vec2<f32>(margin - 1.0, margin - 1.0) - p, vec2<f32>(0.0, 0.0));
+    steer = steer + (far - near) * (params.boundaryScale / margin);
+  }
+  return steer;
+}
+
+// Pushes a position that ended up inside an obstacle back onto its surface.
+fn leave_obstacles(p : vec2<f32>) -> vec2<f32> {
+  let obstacle : vec3<f32> = obstacle_distance(p);
+  if (obstacle.x < 0.0) {
+    return p - obstacle.yz * obstacle.x;
+  }
+  return p;
+}
+
+// The species-weighted sums of the three rules and the fleeing over a particle's neighbours.
+struct Neighbors {
+  cMass : vec2<f32>;
+  cMassWeight : f32;
+  colVel : vec2<f32>;
+  cVel : vec2<f32>;
+  cVelWeight : f32;
+  flee : vec2<f32>;
+};
+
+fn no_neighbors() -> Neighbors {
+  let zero : vec2<f32> = vec2<f32>(0.0, 0.0);
+  return Neighbors(zero, 0.0, zero, zero, 0.0, zero);
+}
+
+// Adds the particle at pos with velocity vel and species other to the sums of a particle of
+// species vSpecies at vPos.
+fn accumulate_neighbor(sums : Neighbors, vPos : vec2<f32>, vSpecies : u32, pos : vec2<f32>, vel : vec2<f32>, other : u32) -> Neighbors {
+  var n : Neighbors = sums;
+  let w : vec4<f32> = species.weights[vSpecies * 4u + other];
+  if (distance(pos, vPos) < params.rule1Distance) {
+    n.cMass = n.cMass + pos * w.x;
+    n.cMassWeight = n.cMassWeight + w.x;
+    if (w.w != 0.0) {
+      let d : vec2<f32> = vPos - pos;
+      n.flee = n.flee + d * (w.w / max(dot(d, d), 0.0025));
+    }
+  }
+  if (distance(pos, vPos) < params.rule2Distance) {
+    n.colVel = n.colVel - (pos - vPos) * w.y;
+  }
+  if (distance(pos, vPos) < params.rule3Distance) {
+    n.cVel = n.cVel + vel * w.z;
+    n.cVelWeight = n.cVelWeight + w.z;
+  }
+  return n;
+}
+
+// The velocity change the rules ask of a particle at vPos with the neighbour sums n.
+fn neighbor_steering(n : Neighbors, vPos : vec2<f32>) -> vec2<f32> {
+  var cMass : vec2<f32> = n.cMass;
+  var cVel : vec2<f32> = n.cVel;
+  if (n.cMassWeight > 0.0) {
+    cMass = (cMass / vec2<f32>(n.cMassWeight, n.cMassWeight)) - vPos;
+  }
+  if (n.cVelWeight > 0.0) {
+    cVel = cVel / vec2<f32>(n.cVelWeight, n.cVelWeight);
+  }
+  return (cMass * params.rule1Scale) + (n.colVel * params.rule2Scale) + (cVel * params.rule3Scale) + n.flee;
+}
+
+// Moves a particle one step along vel, its velocity after the rules and attractors: adds the
+// boundary steering, then wraps around or reflects off the walls as params.boundary says.
+fn integrate(pos : vec2<f32>, vel : vec2<f32>) -> Particle {
+  var vPos : vec2<f32> = pos;
+  var vVel : vec2<f32> = vel + boundary_steering(pos);
+  vVel = normalize(vVel * params.deltaT);
+  vPos = leave_obstacles(vPos + (vVel * params.deltaT));
+  if (params.boundary == 0u) {
+    if (vPos.x < -1.0) {
+      vPos.x = 1.0;
+    }
+    if (vPos.x > 1.0) {
+      vPos.x = -1.0;
+    }
+    if (vPos.y < -1.0) {
+      vPos.y = 1.0;
+    }
+    if (vPos.y > 1.0) {
+      vPos.y = -1.0;
+    }
+  } else {
+    // reflect off the walls; steer mode falls back on this when the steering was not enough
+    if (vPos.x < -1.0) {
+      vPos.x = -2.0 - vPos.x;
+      vVel.x = -vVel.x;
+    }
+    if (vPos.x > 1.0) {
+      vPos.x = 2.0 - vPos.x;
+      vVel.x = -vVel.x;
+    }
+    if (vPos.y < -1.0) {
+      vPos.y = -2.0 - vPos.y;
+      vVel.y = -vVel.y;
+    }
+    if (vPos.y > 1.0) {
+      vPos.y = 2.0 - vPos.y;
+      vVel.y = -vVel.y;
+    }
+  }
+  return Particle(vPos, vVel);
+}
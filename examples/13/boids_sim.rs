@@ -0,0 +1,1449 @@
+#![allow(dead_code)]
+use std::{mem, path::Path};
+use bytemuck::{Pod, Zeroable};
+use serde::{Deserialize, Serialize};
+use wgpu::util::DeviceExt;
+use crate::compute::MAX_WORKGROUPS_PER_DIMENSION;
+use crate::reflect::Reflection;
+use crate::validate::{self, preprocess::Preprocessor, Diagnostic};
+use rand::{
+    distributions::{Distribution, Uniform},
+    SeedableRng,
+};
+
+/// `[pos, vel]`, laid out like `Particle` in boids.wgsl.
+pub type Particle = [[f32; 2]; 2];
+
+pub const PARTICLE_SIZE: u64 = mem::size_of::<Particle>() as u64;
+
+/// Mirrors `SimParams` in boids_shared.wgsl and is uploaded as is.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimParams {
+    pub delta_t: f32,
+    pub rule1_distance: f32,
+    pub rule2_distance: f32,
+    pub rule3_distance: f32,
+    pub rule1_scale: f32,
+    pub rule2_scale: f32,
+    pub rule3_scale: f32,
+    /// One of the `BOUNDARY_*` modes, named in JSON and on the command line.
+    #[serde(with = "boundary_name")]
+    pub boundary: u32,
+    /// Distance from walls (steer mode) and obstacles at which boids start turning away.
+    pub boundary_margin: f32,
+    /// Strength of that turn, reached at the wall or obstacle surface.
+    pub boundary_scale: f32,
+}
+
+pub const BOUNDARY_WRAP: u32 = 0;
+pub const BOUNDARY_REFLECT: u32 = 1;
+pub const BOUNDARY_STEER: u32 = 2;
+/// Names of the boundary modes, indexed by their value.
+pub const BOUNDARY_NAMES: [&str; 3] = ["wrap", "reflect", "steer"];
+
+pub fn parse_boundary(name: &str) -> anyhow::Result<u32> {
+    BOUNDARY_NAMES
+        .iter()
+        .position(|&n| n == name)
+        .map(|i| i as u32)
+        .ok_or_else(|| anyhow::anyhow!("unknown boundary mode {:?}, expected one of {:?}", name, BOUNDARY_NAMES))
+}
+
+mod boundary_name {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(boundary: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(super::BOUNDARY_NAMES.get(*boundary as usize).copied().unwrap_or("wrap"))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        super::parse_boundary(&name).map_err(D::Error::custom)
+    }
+}
+
+impl Default for SimParams {
+    fn default() -> Self {
+        Self {
+            delta_t: 0.04,
+            rule1_distance: 0.1,
+            rule2_distance: 0.025,
+            rule3_distance: 0.025,
+            rule1_scale: 0.02,
+            rule2_scale: 0.05,
+            rule3_scale: 0.025,
+            boundary: BOUNDARY_WRAP,
+            boundary_margin: 0.1,
+            boundary_scale: 0.2,
+        }
+    }
+}
+
+impl SimParams {
+    /// Largest of the three rule distances, the smallest usable grid cell.
+    pub fn max_rule_distance(&self) -> f32 {
+        self.rule1_distance.max(self.rule2_distance).max(self.rule3_distance)
+    }
+
+    /// Reads a JSON object with any subset of the fields, the rest keep their defaults.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let params: Self = serde_json::from_str(&text).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        params.check().map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        Ok(params)
+    }
+
+    /// Rejects values the boundary rules divide by.
+    pub fn check(&self) -> anyhow::Result<()> {
+        if self.boundary_margin.is_nan() || self.boundary_margin <= 0.0 {
+            anyhow::bail!("boundary_margin must be positive, got {}", self.boundary_margin);
+        }
+        Ok(())
+    }
+
+    /// Sets the field named by a command line flag such as `--rule1-distance`.
+    /// Returns `false` if `flag` is not a parameter.
+    pub fn set_flag(&mut self, flag: &str, value: &str) -> anyhow::Result<bool> {
+        if flag == "--boundary" {
+            self.boundary = parse_boundary(value)?;
+            return Ok(true);
+        }
+        let field = match flag {
+            "--delta-t" => &mut self.delta_t,
+            "--rule1-distance" => &mut self.rule1_distance,
+            "--rule2-distance" => &mut self.rule2_distance,
+            "--rule3-distance" => &mut self.rule3_distance,
+            "--rule1-scale" => &mut self.rule1_scale,
+            "--rule2-scale" => &mut self.rule2_scale,
+            "--rule3-scale" => &mut self.rule3_scale,
+            "--boundary-margin" => &mut self.boundary_margin,
+            "--boundary-scale" => &mut self.boundary_scale,
+            _ => return Ok(false),
+        };
+        *field = value
+            .parse()
+            .map_err(|e| anyhow::anyhow!("{} {:?}: {}", flag, value, e))?;
+        self.check().map_err(|e| anyhow::anyhow!("{} {:?}: {}", flag, value, e))?;
+        Ok(true)
+    }
+}
+
+/// Mirrors `Attractors` in boids_shared.wgsl: up to `MAX` points that pull (positive strength)
+/// or push (negative strength) every boid.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
+pub struct Attractors {
+    pub count: u32,
+    _pad: [u32; 3],
+    /// `[x, y, strength, unused]`
+    pub items: [[f32; 4]; 8],
+}
+
+impl Default for Attractors {
+    fn default() -> Self {
+        Self::zeroed()
+    }
+}
+
+impl Attractors {
+    pub const MAX: usize = 8;
+
+    /// Adds a point, dropping the oldest one when all slots are taken.
+    pub fn push(&mut self, pos: [f32; 2], strength: f32) {
+        let count = self.count as usize;
+        if count == Self::MAX {
+            self.items.rotate_left(1);
+            self.items[Self::MAX - 1] = [pos[0], pos[1], strength, 0.0];
+        } else {
+            self.items[count] = [pos[0], pos[1], strength, 0.0];
+            self.count += 1;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn active(&self) -> &[[f32; 4]] {
+        &self.items[..self.count as usize]
+    }
+}
+
+/// Mirrors `Obstacle` in boids_shared.wgsl, a circle or box that boids steer around.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
+pub struct Obstacle {
+    pub center: [f32; 2],
+    /// `[radius, unused]` for circles, half extents for boxes.
+    pub size: [f32; 2],
+    pub kind: u32,
+    _pad: [u32; 3],
+}
+
+impl Obstacle {
+    pub const CIRCLE: u32 = 0;
+    pub const BOX: u32 = 1;
+
+    pub fn circle(center: [f32; 2], radius: f32) -> Self {
+        Self { center, size: [radius, 0.0], kind: Self::CIRCLE, _pad: [0; 3] }
+    }
+
+    pub fn rect(center: [f32; 2], half_size: [f32; 2]) -> Self {
+        Self { center, size: half_size, kind: Self::BOX, _pad: [0; 3] }
+    }
+
+    /// Parses `circle:x,y,radius` or `box:x,y,half_width,half_height`.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let (kind, values) = text.split_once(':').unwrap_or((text, ""));
+        let values = values
+            .split(',')
+            .map(|v| v.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow::anyhow!("obstacle {:?}: {}", text, e))?;
+        match (kind, values.as_slice()) {
+            ("circle", [x, y, r]) => Ok(Self::circle([*x, *y], *r)),
+            ("box", [x, y, w, h]) => Ok(Self::rect([*x, *y], [*w, *h])),
+            _ => Err(anyhow::anyhow!("expected \"circle:x,y,r\" or \"box:x,y,w,h\", got {:?}", text)),
+        }
+    }
+}
+
+/// Mirrors `Obstacles` in boids_shared.wgsl. `sdf` is set by `Simulation::set_sdf`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
+pub struct Obstacles {
+    pub count: u32,
+    pub sdf: u32,
+    _pad: [u32; 2],
+    pub items: [Obstacle; 16],
+}
+
+impl Default for Obstacles {
+    fn default() -> Self {
+        Self::zeroed()
+    }
+}
+
+impl Obstacles {
+    pub const MAX: usize = 16;
+
+    pub fn push(&mut self, obstacle: Obstacle) -> anyhow::Result<()> {
+        if self.count as usize == Self::MAX {
+            anyhow::bail!("at most {} obstacles are supported", Self::MAX);
+        }
+        self.items[self.count as usize] = obstacle;
+        self.count += 1;
+        Ok(())
+    }
+
+    pub fn active(&self) -> &[Obstacle] {
+        &self.items[..self.count as usize]
+    }
+}
+
+/// A signed distance field over [-1, 1]^2, row 0 at y = 1. Distances are in simulation
+/// units and negative inside obstacles.
+#[derive(Clone, Debug)]
+pub struct Sdf {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<f32>,
+}
+
+impl Sdf {
+    /// Builds the field from an image in which dark pixels (luma below 128) are obstacles.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mask = image::open(path)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?
+            .to_luma8();
+        Ok(Self::from_mask(mask.width(), mask.height(), |x, y| mask.get_pixel(x, y)[0] < 128))
+    }
+
+    pub fn from_mask(width: u32, height: u32, inside: impl Fn(u32, u32) -> bool) -> Self {
+        let outside_distance = chamfer_distance(width, height, &inside);
+        let inside_distance = chamfer_distance(width, height, |x, y| !inside(x, y));
+        let texel_size = 2.0 / width.max(height) as f32;
+        let data = outside_distance
+            .iter()
+            .zip(inside_distance.iter())
+            .map(|(&outside, &inside)| {
+                // both are 1 next to the edge; put the zero crossing on the pixel border
+                let texels = if outside > 0.0 { outside - 0.5 } else { 0.5 - inside };
+                texels * texel_size
+            })
+            .collect();
+        Self { width, height, data }
+    }
+
+    fn texel(&self, x: i32, y: i32) -> f32 {
+        let x = x.clamp(0, self.width as i32 - 1) as usize;
+        let y = y.clamp(0, self.height as i32 - 1) as usize;
+        self.data[y * self.width as usize + x]
+    }
+}
+
+/// Mirrors `Species` in boids_shared.wgsl: the per-pair rule weights.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable, Serialize, Deserialize)]
+pub struct SpeciesMatrix {
+    pub count: u32,
+    #[serde(skip)]
+    _pad: [u32; 3],
+    /// `weights[a * MAX + b]` is how species `a` reacts to species `b`:
+    /// `[cohesion, separation, alignment, flee]`.
+    pub weights: [[f32; 4]; 16],
+}
+
+impl Default for SpeciesMatrix {
+    /// A single species that follows the plain rules.
+    fn default() -> Self {
+        let mut matrix = Self::zeroed();
+        matrix.count = 1;
+        matrix.weights[0] = [1.0, 1.0, 1.0, 0.0];
+        matrix
+    }
+}
+
+impl SpeciesMatrix {
+    pub const MAX: usize = 4;
+
+    pub fn weight(&self, a: u32, b: u32) -> [f32; 4] {
+        self.weights[a as usize * Self::MAX + b as usize]
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpeciesInfo {
+    pub name: String,
+    /// Fraction of the particles in this species, relative to the other shares.
+    pub share: f32,
+    /// Blended over the velocity coloring by its alpha.
+    pub color: [f32; 4],
+}
+
+/// A species file: the species and the rows of their interaction matrix.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpeciesConfig {
+    pub species: Vec<SpeciesInfo>,
+    /// `interactions[a][b]` is how species `a` reacts to species `b`:
+    /// `[cohesion, separation, alignment, flee]`. A positive flee weight makes `a` run from
+    /// `b` within `rule1_distance`, which with a cohesion weight from `b` towards `a`
+    /// gives a predator and its prey.
+    pub interactions: Vec<Vec<[f32; 4]>>,
+}
+
+impl Default for SpeciesConfig {
+    fn default() -> Self {
+        Self {
+            species: vec![SpeciesInfo {
+                name: "boid".to_string(),
+                share: 1.0,
+                color: [1.0, 1.0, 1.0, 0.0],
+            }],
+            interactions: vec![vec![[1.0, 1.0, 1.0, 0.0]]],
+        }
+    }
+}
+
+impl SpeciesConfig {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let config: Self = serde_json::from_str(&text).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        config.matrix().map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    pub fn matrix(&self) -> anyhow::Result<SpeciesMatrix> {
+        let count = self.species.len();
+        if count == 0 || count > SpeciesMatrix::MAX {
+            anyhow::bail!("expected 1 to {} species, got {}", SpeciesMatrix::MAX, count);
+        }
+        if self.interactions.len() != count || self.interactions.iter().any(|row| row.len() != count) {
+            anyhow::bail!("interactions must be a {0}x{0} matrix", count);
+        }
+        let mut matrix = SpeciesMatrix::zeroed();
+        matrix.count = count as u32;
+        for (a, row) in self.interactions.iter().enumerate() {
+            for (b, weight) in row.iter().enumerate() {
+                matrix.weights[a * SpeciesMatrix::MAX + b] = *weight;
+            }
+        }
+        Ok(matrix)
+    }
+
+    /// Species ids for `num_particles`, split by share in consecutive runs. The initial
+    /// positions are random, so each species still starts spread over the whole square.
+    pub fn assign(&self, num_particles: u32) -> Vec<u32> {
+        let total: f32 = self.species.iter().map(|s| s.share).sum();
+        let mut ids = Vec::with_capacity(num_particles as usize);
+        let mut cumulative = 0.0;
+        for (id, info) in self.species.iter().enumerate() {
+            cumulative += info.share;
+            let end = ((cumulative / total) * num_particles as f32).round() as usize;
+            ids.resize(end.clamp(ids.len(), num_particles as usize), id as u32);
+        }
+        ids.resize(num_particles as usize, self.species.len() as u32 - 1);
+        ids
+    }
+
+    pub fn colors(&self) -> [[f32; 4]; 4] {
+        let mut colors = [[0.0; 4]; 4];
+        for (color, info) in colors.iter_mut().zip(self.species.iter()) {
+            *color = info.color;
+        }
+        colors
+    }
+}
+
+/// Approximate distance in pixels from every pixel to the nearest `seed` pixel, using a
+/// two-pass chamfer transform with 1 and sqrt(2) steps.
+fn chamfer_distance(width: u32, height: u32, seed: impl Fn(u32, u32) -> bool) -> Vec<f32> {
+    let (w, h) = (width as i32, height as i32);
+    let far = (w + h) as f32 * 2.0;
+    let mut d = (0..h)
+        .flat_map(|y| (0..w).map(move |x| (x, y)))
+        .map(|(x, y)| if seed(x as u32, y as u32) { 0.0 } else { far })
+        .collect::<Vec<f32>>();
+    let diagonal = std::f32::consts::SQRT_2;
+    let mut relax = |x: i32, y: i32, neighbors: [(i32, i32, f32); 4]| {
+        let mut best = d[(y * w + x) as usize];
+        for (dx, dy, step) in neighbors {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx >= 0 && ny >= 0 && nx < w && ny < h {
+                best = best.min(d[(ny * w + nx) as usize] + step);
+            }
+        }
+        d[(y * w + x) as usize] = best;
+    };
+    for y in 0..h {
+        for x in 0..w {
+            relax(x, y, [(-1, 0, 1.0), (0, -1, 1.0), (-1, -1, diagonal), (1, -1, diagonal)]);
+        }
+    }
+    for y in (0..h).rev() {
+        for x in (0..w).rev() {
+            relax(x, y, [(1, 0, 1.0), (0, 1, 1.0), (1, 1, diagonal), (-1, 1, diagonal)]);
+        }
+    }
+    d
+}
+
+/// Positions uniform in [-1, 1], velocities uniform in [-0.1, 0.1].
+pub fn initial_particles(num_particles: u32, seed: u64) -> Vec<Particle> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let unif = Uniform::new_inclusive(-1.0, 1.0);
+    (0..num_particles)
+        .map(|_| {
+            let pos = [unif.sample(&mut rng), unif.sample(&mut rng)];
+            let vel = [unif.sample(&mut rng) * 0.1, unif.sample(&mut rng) * 0.1];
+            [pos, vel]
+        })
+        .collect()
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    ((a[0] - b[0]) * (a[0] - b[0]) + (a[1] - b[1]) * (a[1] - b[1])).sqrt()
+}
+
+/// WGSL `sign`, which unlike `f32::signum` is 0 at 0.
+fn sign(x: f32) -> f32 {
+    if x > 0.0 {
+        1.0
+    } else if x < 0.0 {
+        -1.0
+    } else {
+        0.0
+    }
+}
+
+/// CPU version of `obstacle_distance` in boids.wgsl: the signed distance to the closest
+/// obstacle and the direction away from it.
+fn obstacle_distance(obstacles: &Obstacles, sdf: Option<&Sdf>, p: [f32; 2]) -> (f32, [f32; 2]) {
+    let mut closest = (1000.0, [0.0, 0.0]);
+    for item in obstacles.active() {
+        let rel = [p[0] - item.center[0], p[1] - item.center[1]];
+        let (d, n) = if item.kind == Obstacle::CIRCLE {
+            let len = (rel[0] * rel[0] + rel[1] * rel[1]).sqrt();
+            (len - item.size[0], [rel[0] / len.max(0.0001), rel[1] / len.max(0.0001)])
+        } else {
+            let q = [rel[0].abs() - item.size[0], rel[1].abs() - item.size[1]];
+            let outside = [q[0].max(0.0), q[1].max(0.0)];
+            let len = (outside[0] * outside[0] + outside[1] * outside[1]).sqrt();
+            let n = if len > 0.0 {
+                [sign(rel[0]) * outside[0] / len, sign(rel[1]) * outside[1] / len]
+            } else if q[0] > q[1] {
+                [sign(rel[0]), 0.0]
+            } else {
+                [0.0, sign(rel[1])]
+            };
+            (len + q[0].max(q[1]).min(0.0), n)
+        };
+        if d < closest.0 {
+            closest = (d, n);
+        }
+    }
+    if let Some(sdf) = sdf.filter(|_| obstacles.sdf != 0) {
+        let uv = [(p[0] * 0.5 + 0.5) * sdf.width as f32, (0.5 - p[1] * 0.5) * sdf.height as f32];
+        let (x, y) = (uv[0].floor() as i32, uv[1].floor() as i32);
+        let d = sdf.texel(x, y);
+        let g = [sdf.texel(x + 1, y) - sdf.texel(x - 1, y), sdf.texel(x, y - 1) - sdf.texel(x, y + 1)];
+        let len2 = g[0] * g[0] + g[1] * g[1];
+        if d < closest.0 && len2 > 0.0 {
+            let len = len2.sqrt();
+            closest = (d, [g[0] / len, g[1] / len]);
+        }
+    }
+    closest
+}
+
+/// CPU version of `boundary_steering` in boids.wgsl.
+fn boundary_steering(params: &SimParams, obstacles: &Obstacles, sdf: Option<&Sdf>, p: [f32; 2]) -> [f32; 2] {
+    let mut steer = [0.0f32, 0.0];
+    let margin = params.boundary_margin;
+    let (d, n) = obstacle_distance(obstacles, sdf, p);
+    if d < margin {
+        let scale = params.boundary_scale * (1.0 - d / margin);
+        steer = [steer[0] + n[0] * scale, steer[1] + n[1] * scale];
+    }
+    if params.boundary == BOUNDARY_STEER {
+        for k in 0..2 {
+            let near = (p[k] - (1.0 - margin)).max(0.0);
+            let far = ((margin - 1.0) - p[k]).max(0.0);
+            steer[k] += (far - near) * (params.boundary_scale / margin);
+        }
+    }
+    steer
+}
+
+/// CPU version of `cs_main`, kept operation for operation in step with the shader.
+#[allow(clippy::too_many_arguments)]
+pub fn cpu_step(
+    params: &SimParams,
+    attractors: &Attractors,
+    obstacles: &Obstacles,
+    sdf: Option<&Sdf>,
+    species: &SpeciesMatrix,
+    species_ids: &[u32],
+    src: &[Particle],
+    dst: &mut [Particle],
+) {
+    for (index, out) in dst.iter_mut().enumerate() {
+        let [mut v_pos, mut v_vel] = src[index];
+        let mut c_mass = [0.0f32, 0.0];
+        let mut c_vel = [0.0f32, 0.0];
+        let mut col_vel = [0.0f32, 0.0];
+        let mut c_mass_weight = 0.0f32;
+        let mut c_vel_weight = 0.0f32;
+        let mut flee = [0.0f32, 0.0];
+        let v_species = species_ids[index];
+
+        for (i, &[pos, vel]) in src.iter().enumerate() {
+            if i == index {
+                continue;
+            }
+            let w = species.weight(v_species, species_ids[i]);
+            if distance(pos, v_pos) < params.rule1_distance {
+                c_mass = [c_mass[0] + pos[0] * w[0], c_mass[1] + pos[1] * w[0]];
+                c_mass_weight += w[0];
+                if w[3] != 0.0 {
+                    let d = [v_pos[0] - pos[0], v_pos[1] - pos[1]];
+                    let falloff = w[3] / (d[0] * d[0] + d[1] * d[1]).max(0.0025);
+                    flee = [flee[0] + d[0] * falloff, flee[1] + d[1] * falloff];
+                }
+            }
+            if distance(pos, v_pos) < params.rule2_distance {
+                col_vel = [col_vel[0] - (pos[0] - v_pos[0]) * w[1], col_vel[1] - (pos[1] - v_pos[1]) * w[1]];
+            }
+            if distance(pos, v_pos) < params.rule3_distance {
+                c_vel = [c_vel[0] + vel[0] * w[2], c_vel[1] + vel[1] * w[2]];
+                c_vel_weight += w[2];
+            }
+        }
+        if c_mass_weight > 0.0 {
+            c_mass = [c_mass[0] / c_mass_weight - v_pos[0], c_mass[1] / c_mass_weight - v_pos[1]];
+        }
+        if c_vel_weight > 0.0 {
+            c_vel = [c_vel[0] / c_vel_weight, c_vel[1] / c_vel_weight];
+        }
+        for k in 0..2 {
+            v_vel[k] = v_vel[k] + (c_mass[k] * params.rule1_scale) + (col_vel[k] * params.rule2_scale)
+                + (c_vel[k] * params.rule3_scale) + flee[k];
+        }
+        for item in attractors.active() {
+            let d = [item[0] - v_pos[0], item[1] - v_pos[1]];
+            let falloff = item[2] / (d[0] * d[0] + d[1] * d[1]).max(0.0025);
+            v_vel = [v_vel[0] + d[0] * falloff, v_vel[1] + d[1] * falloff];
+        }
+        let steer = boundary_steering(params, obstacles, sdf, v_pos);
+        v_vel = [v_vel[0] + steer[0], v_vel[1] + steer[1]];
+        let scaled = [v_vel[0] * params.delta_t, v_vel[1] * params.delta_t];
+        let len = (scaled[0] * scaled[0] + scaled[1] * scaled[1]).sqrt();
+        v_vel = [scaled[0] / len, scaled[1] / len];
+        v_pos = [v_pos[0] + v_vel[0] * params.delta_t, v_pos[1] + v_vel[1] * params.delta_t];
+        let (d, n) = obstacle_distance(obstacles, sdf, v_pos);
+        if d < 0.0 {
+            v_pos = [v_pos[0] - n[0] * d, v_pos[1] - n[1] * d];
+        }
+        for k in 0..2 {
+            if params.boundary == BOUNDARY_WRAP {
+                if v_pos[k] < -1.0 {
+                    v_pos[k] = 1.0;
+                }
+                if v_pos[k] > 1.0 {
+                    v_pos[k] = -1.0;
+                }
+            } else {
+                if v_pos[k] < -1.0 {
+                    v_pos[k] = -2.0 - v_pos[k];
+                    v_vel[k] = -v_vel[k];
+                }
+                if v_pos[k] > 1.0 {
+                    v_pos[k] = 2.0 - v_pos[k];
+                    v_vel[k] = -v_vel[k];
+                }
+            }
+        }
+        *out = [v_pos, v_vel];
+    }
+}
+
+/// Mirrors `GridParams` in boids_grid.wgsl.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
+pub struct GridParams {
+    pub dim: u32,
+    pub num_cells: u32,
+    pub cell_size: f32,
+}
+
+impl GridParams {
+    pub const MAX_DIM: u32 = 1024;
+
+    /// Square grid over [-1, 1]^2 whose cells are at least as wide as every rule distance,
+    /// so all neighbors of a boid lie in the 3x3 cells around it.
+    pub fn for_params(params: &SimParams) -> Self {
+        let dim = ((2.0 / params.max_rule_distance()).floor() as u32).clamp(1, Self::MAX_DIM);
+        Self {
+            dim,
+            num_cells: dim * dim,
+            cell_size: 2.0 / dim as f32,
+        }
+    }
+}
+
+/// Buffers and pipelines for the binned neighbor search in boids_grid.wgsl.
+pub struct Grid {
+    pub params: GridParams,
+    pub grid_buffer: wgpu::Buffer,
+    pub cells_buffer: wgpu::Buffer,
+    pub keys_buffer: wgpu::Buffer,
+    pub sorted_buffer: wgpu::Buffer,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_groups: Vec<wgpu::BindGroup>,
+    pub pipeline_layout: wgpu::PipelineLayout,
+    pub clear_pipeline: wgpu::ComputePipeline,
+    pub count_pipeline: wgpu::ComputePipeline,
+    pub scan_pipeline: wgpu::ComputePipeline,
+    pub scatter_pipeline: wgpu::ComputePipeline,
+    pub main_pipeline: wgpu::ComputePipeline,
+}
+
+impl Grid {
+    const ENTRY_POINTS: [&'static str; 5] = ["grid_clear", "grid_count", "grid_scan", "grid_scatter", "cs_grid_main"];
+
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        params: GridParams,
+        param_buffer: &wgpu::Buffer,
+        attractor_buffer: &wgpu::Buffer,
+        particle_buffers: &[wgpu::Buffer],
+        shared_bind_group_layouts: &[&wgpu::BindGroupLayout],
+        num_particles: u32,
+    ) -> Self {
+        let grid_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Grid Param Buffer"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let cells_buffer = Self::create_cells_buffer(device, &params);
+        let keys_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Grid Keys Buffer"),
+            size: num_particles as u64 * mem::size_of::<[u32; 2]>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let sorted_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Grid Sorted Buffer"),
+            size: num_particles as u64 * mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        // groups 1 and 2 are the simulation's, shared with boids.wgsl
+        let reflection = reflect_shader("boids_grid.wgsl", include_str!("boids_grid.wgsl"), &Self::ENTRY_POINTS);
+        Self::check_buffer_sizes(&reflection, &params, num_particles).unwrap_or_else(|e| panic!("{:#}", e));
+        let bind_group_layout = reflection
+            .bind_group_layouts(device)
+            .unwrap_or_else(|e| panic!("{:#}", e))
+            .swap_remove(0);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Grid"),
+            bind_group_layouts: &[&[&bind_group_layout], shared_bind_group_layouts].concat(),
+            push_constant_ranges: &[],
+        });
+        let [clear_pipeline, count_pipeline, scan_pipeline, scatter_pipeline, main_pipeline] =
+            Self::create_pipelines(device, &pipeline_layout, shader);
+
+        let mut grid = Self {
+            params,
+            grid_buffer,
+            cells_buffer,
+            keys_buffer,
+            sorted_buffer,
+            bind_group_layout,
+            bind_groups: Vec::new(),
+            pipeline_layout,
+            clear_pipeline,
+            count_pipeline,
+            scan_pipeline,
+            scatter_pipeline,
+            main_pipeline,
+        };
+        grid.create_bind_groups(device, param_buffer, attractor_buffer, particle_buffers);
+        grid
+    }
+
+    /// Checks the buffers `new` creates for `params` and `num_particles` against group 0 of
+    /// boids_grid.wgsl.
+    pub fn check_buffer_sizes(reflection: &Reflection, params: &GridParams, num_particles: u32) -> anyhow::Result<()> {
+        let num_particles = num_particles as u64;
+        reflection.check_buffer_size(0, 0, mem::size_of::<SimParams>() as u64)?;
+        reflection.check_buffer_size(0, 1, num_particles * PARTICLE_SIZE)?;
+        reflection.check_buffer_size(0, 2, num_particles * PARTICLE_SIZE)?;
+        reflection.check_buffer_size(0, 3, mem::size_of::<GridParams>() as u64)?;
+        reflection.check_buffer_size(0, 4, (params.num_cells as u64 + 1) * mem::size_of::<u32>() as u64)?;
+        reflection.check_buffer_size(0, 5, num_particles * mem::size_of::<[u32; 2]>() as u64)?;
+        reflection.check_buffer_size(0, 6, num_particles * mem::size_of::<u32>() as u64)?;
+        reflection.check_buffer_size(0, 7, mem::size_of::<Attractors>() as u64)?;
+        Ok(())
+    }
+
+    /// The clear, count, scan, scatter and main passes, in that order.
+    fn create_pipelines(
+        device: &wgpu::Device,
+        pipeline_layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+    ) -> [wgpu::ComputePipeline; 5] {
+        Self::ENTRY_POINTS.map(|entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(pipeline_layout),
+                module: shader,
+                entry_point,
+            })
+        })
+    }
+
+    fn create_cells_buffer(device: &wgpu::Device, params: &GridParams) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Grid Cells Buffer"),
+            size: (params.num_cells as u64 + 1) * mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_bind_groups(
+        &mut self,
+        device: &wgpu::Device,
+        param_buffer: &wgpu::Buffer,
+        attractor_buffer: &wgpu::Buffer,
+        particle_buffers: &[wgpu::Buffer],
+    ) {
+        self.bind_groups = (0..2)
+            .map(|i| device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: param_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: particle_buffers[i % 2].as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: particle_buffers[(i + 1) % 2].as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: self.grid_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: self.cells_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 5,
+                        resource: self.keys_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 6,
+                        resource: self.sorted_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 7,
+                        resource: attractor_buffer.as_entire_binding(),
+                    },
+                ],
+                label: Some("Grid Bind Group"),
+            }))
+            .collect();
+    }
+
+    /// Re-fits the grid to new rule distances, growing the cell buffer when needed.
+    fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        params: GridParams,
+        param_buffer: &wgpu::Buffer,
+        attractor_buffer: &wgpu::Buffer,
+        particle_buffers: &[wgpu::Buffer],
+    ) {
+        if params.num_cells > self.params.num_cells {
+            self.cells_buffer = Self::create_cells_buffer(device, &params);
+            self.create_bind_groups(device, param_buffer, attractor_buffer, particle_buffers);
+        }
+        queue.write_buffer(&self.grid_buffer, 0, bytemuck::bytes_of(&params));
+        self.params = params;
+    }
+}
+
+/// Compiles a compute shader with `WORKGROUP_SIZE` defined as `workgroup_size`, since WGSL here
+/// has no pipeline-overridable constants. Dispatch counts must use the same value.
+/// `file` names the shader in validation errors.
+pub fn compute_shader(
+    device: &wgpu::Device,
+    preprocessor: &Preprocessor,
+    file: &str,
+    source: &str,
+    workgroup_size: u32,
+) -> Result<wgpu::ShaderModule, Diagnostic> {
+    let preprocessor = preprocessor.clone().define("WORKGROUP_SIZE", workgroup_size);
+    validate::try_shader_module_with(device, &preprocessor, file, source)
+}
+
+/// `compute_shader` for one of the built-in shaders, which are known to be valid.
+fn builtin_compute_shader(device: &wgpu::Device, file: &str, source: &str, workgroup_size: u32) -> wgpu::ShaderModule {
+    compute_shader(device, &Preprocessor::new(), file, source, workgroup_size).unwrap_or_else(|diagnostic| panic!("{}", diagnostic))
+}
+
+/// Reflects one of the built-in shaders, which are known to be valid.
+fn reflect_shader(file: &str, source: &str, entry_points: &[&str]) -> Reflection {
+    Reflection::new(file, source, entry_points).unwrap_or_else(|e| panic!("{:#}", e))
+}
+
+/// The `sdfTexture` binding; a 1x1 placeholder when there is no field, which the shaders
+/// then ignore since `Obstacles::sdf` is 0.
+fn create_sdf_view(device: &wgpu::Device, queue: &wgpu::Queue, sdf: Option<&Sdf>) -> wgpu::TextureView {
+    let placeholder = Sdf { width: 1, height: 1, data: vec![0.0] };
+    let sdf = sdf.unwrap_or(&placeholder);
+    let texture = device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: Some("SDF Texture"),
+            size: wgpu::Extent3d {
+                width: sdf.width,
+                height: sdf.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        },
+        bytemuck::cast_slice(&sdf.data),
+    );
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// Compute pipelines compiled from edited shader sources by `Simulation::build_pipelines`.
+pub struct Pipelines {
+    compute: wgpu::ComputePipeline,
+    grid: Option<[wgpu::ComputePipeline; 5]>,
+}
+
+/// The compute half of the boids example: the parameter uniform, the ping-pong particle buffers
+/// and the `cs_main` pipeline that advances one into the other.
+pub struct Simulation {
+    pub params: SimParams,
+    pub param_buffer: wgpu::Buffer,
+    pub attractors: Attractors,
+    pub attractor_buffer: wgpu::Buffer,
+    pub obstacles: Obstacles,
+    pub obstacle_buffer: wgpu::Buffer,
+    pub sdf: Option<Sdf>,
+    /// Group 1 of both compute shaders: the obstacles and the SDF texture.
+    pub boundary_bind_group_layout: wgpu::BindGroupLayout,
+    pub boundary_bind_group: wgpu::BindGroup,
+    pub species: SpeciesMatrix,
+    pub species_buffer: wgpu::Buffer,
+    /// One id per particle, also bound as a per-instance vertex buffer for coloring.
+    pub species_id_buffer: wgpu::Buffer,
+    pub species_ids: Vec<u32>,
+    /// Group 2 of both compute shaders: the species matrix and ids.
+    pub species_bind_group_layout: wgpu::BindGroupLayout,
+    pub species_bind_group: wgpu::BindGroup,
+    pub particle_buffers: Vec<wgpu::Buffer>,
+    pub particle_bind_groups: Vec<wgpu::BindGroup>,
+    pub compute_pipeline_layout: wgpu::PipelineLayout,
+    pub compute_pipeline: wgpu::ComputePipeline,
+    pub grid: Option<Grid>,
+    pub num_particles: u32,
+    pub workgroup_size: u32,
+    pub work_group_count: u32,
+    pub frame_num: usize,
+}
+
+impl Simulation {
+    pub const DEFAULT_WORKGROUP_SIZE: u32 = 64;
+    /// `max_compute_invocations_per_workgroup` in the default limits.
+    pub const MAX_WORKGROUP_SIZE: u32 = 256;
+    /// The most particles one dispatch of `workgroup_size` covers whose buffers `limits` can
+    /// bind; the particle buffers are the largest.
+    pub fn max_particles(limits: &wgpu::Limits, workgroup_size: u32) -> u32 {
+        let per_binding = limits.max_storage_buffer_binding_size as u64 / PARTICLE_SIZE;
+        MAX_WORKGROUPS_PER_DIMENSION.saturating_mul(workgroup_size).min(per_binding as u32)
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        params: SimParams,
+        particles: &[Particle],
+        workgroup_size: u32,
+    ) -> Self {
+        let num_particles = particles.len() as u32;
+        let shader = builtin_compute_shader(device, "boids.wgsl", include_str!("boids.wgsl"), workgroup_size);
+        let reflection = reflect_shader("boids.wgsl", include_str!("boids.wgsl"), &["cs_main"]);
+        Self::check_buffer_sizes(&reflection, num_particles).unwrap_or_else(|e| panic!("{:#}", e));
+        let bind_group_layouts = reflection
+            .bind_group_layouts(device)
+            .unwrap_or_else(|e| panic!("{:#}", e));
+        let compute_pipeline_layout = reflection.pipeline_layout(device, &bind_group_layouts);
+        let Ok([compute_bind_group_layout, boundary_bind_group_layout, species_bind_group_layout]) =
+            <[wgpu::BindGroupLayout; 3]>::try_from(bind_group_layouts)
+        else {
+            panic!("boids.wgsl should use bind groups 0, 1 and 2");
+        };
+
+        let param_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Param Buffer"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let attractors = Attractors::default();
+        let attractor_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Attractor Buffer"),
+            contents: bytemuck::bytes_of(&attractors),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let obstacles = Obstacles::default();
+        let obstacle_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Obstacle Buffer"),
+            contents: bytemuck::bytes_of(&obstacles),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let boundary_bind_group = Self::create_boundary_bind_group(
+            device,
+            &boundary_bind_group_layout,
+            &obstacle_buffer,
+            &create_sdf_view(device, queue, None),
+        );
+
+        let species = SpeciesMatrix::default();
+        let species_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Species Buffer"),
+            contents: bytemuck::bytes_of(&species),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let species_ids = vec![0u32; particles.len()];
+        let species_id_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Species Id Buffer"),
+            contents: bytemuck::cast_slice(&species_ids),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let species_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &species_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: species_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: species_id_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("Species Bind Group"),
+        });
+
+        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Compute Pipeline"),
+            layout: Some(&compute_pipeline_layout),
+            module: &shader,
+            entry_point: "cs_main",
+        });
+
+        let mut particle_buffers = Vec::<wgpu::Buffer>::new();
+        let mut particle_bind_groups = Vec::<wgpu::BindGroup>::new();
+        for i in 0..2 {
+            particle_buffers.push(
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("Particle Buffer {}", i)),
+                    contents: bytemuck::cast_slice(particles),
+                    usage: wgpu::BufferUsages::VERTEX
+                        | wgpu::BufferUsages::STORAGE
+                        | wgpu::BufferUsages::COPY_DST
+                        | wgpu::BufferUsages::COPY_SRC,
+                }),
+            );
+        }
+
+        for i in 0..2 {
+            particle_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &compute_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: param_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: particle_buffers[i % 2].as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: particle_buffers[(i + 1) % 2].as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: attractor_buffer.as_entire_binding(),
+                    },
+                ],
+                label: None,
+            }));
+        }
+        let work_group_count = num_particles.div_ceil(workgroup_size);
+
+        Self {
+            params,
+            param_buffer,
+            attractors,
+            attractor_buffer,
+            obstacles,
+            obstacle_buffer,
+            sdf: None,
+            boundary_bind_group_layout,
+            boundary_bind_group,
+            species,
+            species_buffer,
+            species_id_buffer,
+            species_ids,
+            species_bind_group_layout,
+            species_bind_group,
+            particle_buffers,
+            particle_bind_groups,
+            compute_pipeline_layout,
+            compute_pipeline,
+            grid: None,
+            num_particles,
+            workgroup_size,
+            work_group_count,
+            frame_num: 0,
+        }
+    }
+
+    /// Switches `step` from the O(n^2) `cs_main` to the binned passes in boids_grid.wgsl,
+    /// sized for the current rule distances.
+    pub fn enable_grid(&mut self, device: &wgpu::Device) {
+        let grid_shader = builtin_compute_shader(device, "boids_grid.wgsl", include_str!("boids_grid.wgsl"), self.workgroup_size);
+        self.grid = Some(Grid::new(
+            device,
+            &grid_shader,
+            GridParams::for_params(&self.params),
+            &self.param_buffer,
+            &self.attractor_buffer,
+            &self.particle_buffers,
+            &[&self.boundary_bind_group_layout, &self.species_bind_group_layout],
+            self.num_particles,
+        ));
+    }
+
+    /// Checks the buffers `new` creates for `num_particles` against boids.wgsl.
+    pub fn check_buffer_sizes(reflection: &Reflection, num_particles: u32) -> anyhow::Result<()> {
+        let num_particles = num_particles as u64;
+        reflection.check_buffer_size(0, 0, mem::size_of::<SimParams>() as u64)?;
+        reflection.check_buffer_size(0, 1, num_particles * PARTICLE_SIZE)?;
+        reflection.check_buffer_size(0, 2, num_particles * PARTICLE_SIZE)?;
+        reflection.check_buffer_size(0, 3, mem::size_of::<Attractors>() as u64)?;
+        reflection.check_buffer_size(1, 0, mem::size_of::<Obstacles>() as u64)?;
+        reflection.check_buffer_size(2, 0, mem::size_of::<SpeciesMatrix>() as u64)?;
+        reflection.check_buffer_size(2, 1, num_particles * mem::size_of::<u32>() as u64)?;
+        Ok(())
+    }
+
+    /// Compiles boids.wgsl and boids_grid.wgsl sources, e.g. edited copies read from disk, for this
+    /// simulation's layouts and workgroup size, or returns the diagnostic of the first that does not
+    /// validate. The grid source is only used with the grid enabled.
+    pub fn build_pipelines(
+        &self,
+        device: &wgpu::Device,
+        preprocessor: &Preprocessor,
+        source: &str,
+        grid_source: &str,
+    ) -> Result<Pipelines, Diagnostic> {
+        let shader = compute_shader(device, preprocessor, "boids.wgsl", source, self.workgroup_size)?;
+        let compute = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Compute Pipeline"),
+            layout: Some(&self.compute_pipeline_layout),
+            module: &shader,
+            entry_point: "cs_main",
+        });
+        let grid = match &self.grid {
+            Some(grid) => {
+                let grid_shader = compute_shader(device, preprocessor, "boids_grid.wgsl", grid_source, self.workgroup_size)?;
+                Some(Grid::create_pipelines(device, &grid.pipeline_layout, &grid_shader))
+            }
+            None => None,
+        };
+        Ok(Pipelines { compute, grid })
+    }
+
+    /// Swaps in pipelines from `build_pipelines`, keeping every buffer and so the simulation state.
+    pub fn set_pipelines(&mut self, pipelines: Pipelines) {
+        self.compute_pipeline = pipelines.compute;
+        if let (Some(grid), Some([clear, count, scan, scatter, main])) = (&mut self.grid, pipelines.grid) {
+            grid.clear_pipeline = clear;
+            grid.count_pipeline = count;
+            grid.scan_pipeline = scan;
+            grid.scatter_pipeline = scatter;
+            grid.main_pipeline = main;
+        }
+    }
+
+    /// Uploads new parameters, taking effect from the next `step`.
+    pub fn set_params(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, params: SimParams) {
+        queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&params));
+        if let Some(grid) = &mut self.grid {
+            let grid_params = GridParams::for_params(&params);
+            if grid_params != grid.params {
+                grid.resize(
+                    device,
+                    queue,
+                    grid_params,
+                    &self.param_buffer,
+                    &self.attractor_buffer,
+                    &self.particle_buffers,
+                );
+            }
+        }
+        self.params = params;
+    }
+
+    pub fn set_attractors(&mut self, queue: &wgpu::Queue, attractors: Attractors) {
+        queue.write_buffer(&self.attractor_buffer, 0, bytemuck::bytes_of(&attractors));
+        self.attractors = attractors;
+    }
+
+    pub fn set_obstacles(&mut self, queue: &wgpu::Queue, obstacles: Obstacles) {
+        self.obstacles = Obstacles { sdf: self.obstacles.sdf, ..obstacles };
+        queue.write_buffer(&self.obstacle_buffer, 0, bytemuck::bytes_of(&self.obstacles));
+    }
+
+    /// Replaces the signed distance field boids avoid in addition to the obstacles, or removes it.
+    pub fn set_sdf(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, sdf: Option<Sdf>) {
+        self.boundary_bind_group = Self::create_boundary_bind_group(
+            device,
+            &self.boundary_bind_group_layout,
+            &self.obstacle_buffer,
+            &create_sdf_view(device, queue, sdf.as_ref()),
+        );
+        self.obstacles.sdf = sdf.is_some() as u32;
+        queue.write_buffer(&self.obstacle_buffer, 0, bytemuck::bytes_of(&self.obstacles));
+        self.sdf = sdf;
+    }
+
+    /// Uploads a new interaction matrix and one species id per particle.
+    pub fn set_species(&mut self, queue: &wgpu::Queue, species: SpeciesMatrix, species_ids: Vec<u32>) {
+        assert_eq!(species_ids.len(), self.num_particles as usize, "one species id per particle");
+        queue.write_buffer(&self.species_buffer, 0, bytemuck::bytes_of(&species));
+        queue.write_buffer(&self.species_id_buffer, 0, bytemuck::cast_slice(&species_ids));
+        self.species = species;
+        self.species_ids = species_ids;
+    }
+
+    fn create_boundary_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        obstacle_buffer: &wgpu::Buffer,
+        sdf_view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: obstacle_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(sdf_view),
+                },
+            ],
+            label: Some("Boundary Bind Group"),
+        })
+    }
+
+    /// Overwrites both particle buffers and restarts the frame count.
+    pub fn reset(&mut self, queue: &wgpu::Queue, particles: &[Particle]) {
+        for buffer in self.particle_buffers.iter() {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(particles));
+        }
+        self.frame_num = 0;
+    }
+
+    /// Records one simulation step; afterwards `current_buffer` holds its output.
+    pub fn step(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let mut compute_pass = encoder.begin_compute_pass(
+            &wgpu::ComputePassDescriptor { label: None });
+        compute_pass.set_bind_group(1, &self.boundary_bind_group, &[]);
+        compute_pass.set_bind_group(2, &self.species_bind_group, &[]);
+        match &self.grid {
+            Some(grid) => {
+                let cell_group_count = (grid.params.num_cells + 1).div_ceil(self.workgroup_size);
+                compute_pass.set_bind_group(0, &grid.bind_groups[self.frame_num % 2], &[]);
+                compute_pass.set_pipeline(&grid.clear_pipeline);
+                compute_pass.dispatch(cell_group_count, 1, 1);
+                compute_pass.set_pipeline(&grid.count_pipeline);
+                compute_pass.dispatch(self.work_group_count, 1, 1);
+                compute_pass.set_pipeline(&grid.scan_pipeline);
+                compute_pass.dispatch(1, 1, 1);
+                compute_pass.set_pipeline(&grid.scatter_pipeline);
+                compute_pass.dispatch(self.work_group_count, 1, 1);
+                compute_pass.set_pipeline(&grid.main_pipeline);
+                compute_pass.dispatch(self.work_group_count, 1, 1);
+            }
+            None => {
+                compute_pass.set_pipeline(&self.compute_pipeline);
+                compute_pass.set_bind_group(0, &self.particle_bind_groups[self.frame_num % 2], &[]);
+                compute_pass.dispatch(self.work_group_count, 1, 1);
+            }
+        }
+        drop(compute_pass);
+        self.frame_num += 1;
+    }
+
+    /// The buffer written by the most recent `step`, i.e. the one to draw.
+    pub fn current_buffer(&self) -> &wgpu::Buffer {
+        &self.particle_buffers[self.frame_num % 2]
+    }
+
+    pub async fn read_particles(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<Vec<Particle>> {
+        let size = self.num_particles as u64 * PARTICLE_SIZE;
+        let read_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Read Buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(self.current_buffer(), 0, &read_buffer, 0, size);
+        queue.submit(Some(encoder.finish()));
+
+        let read_buffer_slice = read_buffer.slice(..);
+        let read_buffer_future = read_buffer_slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        read_buffer_future.await?;
+        let data = read_buffer_slice.get_mapped_range();
+        let particles = bytemuck::cast_slice(&data).to_vec();
+        drop(data);
+        read_buffer.unmap();
+        Ok(particles)
+    }
+
+    /// Reads the current particles back into a snapshot of the whole simulation state.
+    pub async fn snapshot(&self, device: &wgpu::Device, queue: &wgpu::Queue, seed: u64) -> anyhow::Result<Snapshot> {
+        Ok(Snapshot {
+            version: Snapshot::VERSION,
+            seed,
+            frame: self.frame_num as u64,
+            params: self.params,
+            species: self.species,
+            species_ids: self.species_ids.clone(),
+            particles: self.read_particles(device, queue).await?,
+        })
+    }
+
+    /// Puts the particles, species and frame count of `snapshot` back. The parameters are left
+    /// to the caller, since they usually go through `set_params` with local changes applied.
+    pub fn restore(&mut self, queue: &wgpu::Queue, snapshot: &Snapshot) -> anyhow::Result<()> {
+        if snapshot.particles.len() != self.num_particles as usize {
+            anyhow::bail!(
+                "snapshot has {} particles, the simulation {}",
+                snapshot.particles.len(),
+                self.num_particles,
+            );
+        }
+        self.reset(queue, &snapshot.particles);
+        self.set_species(queue, snapshot.species, snapshot.species_ids.clone());
+        self.frame_num = snapshot.frame as usize;
+        Ok(())
+    }
+}
+
+/// Everything needed to continue a run: the particles, the rules they follow, the frame
+/// number and the seed the run started from.
+///
+/// Saved as pretty JSON for `.json` paths and in a compact binary format otherwise:
+/// `MAGIC`, then little-endian `version: u32`, `seed: u64`, `frame: u64`, the `SimParams`
+/// and `SpeciesMatrix` uniforms as uploaded but with every 32-bit word little-endian,
+/// `num_particles: u32`, the particles and their species ids.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub seed: u64,
+    pub frame: u64,
+    pub params: SimParams,
+    pub species: SpeciesMatrix,
+    pub species_ids: Vec<u32>,
+    pub particles: Vec<Particle>,
+}
+
+impl Snapshot {
+    /// Bumped whenever the layout of the particles or of one of the uniforms changes.
+    pub const VERSION: u32 = 1;
+    pub const MAGIC: &'static [u8; 8] = b"BOIDSNAP";
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let bytes = if is_json(path) {
+            serde_json::to_vec_pretty(self)?
+        } else {
+            self.to_bytes()
+        };
+        std::fs::write(path, bytes).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        let snapshot = if is_json(path) {
+            serde_json::from_slice::<Self>(&bytes).map_err(anyhow::Error::from)
+        } else {
+            Self::from_bytes(&bytes)
+        };
+        let snapshot = snapshot.and_then(|snapshot| snapshot.check().map(|()| snapshot));
+        snapshot.map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+    }
+
+    /// Rejects snapshots `Simulation::restore` could not run: an unknown version, bad
+    /// parameters, or species ids that do not fit the particles or the species matrix.
+    pub fn check(&self) -> anyhow::Result<()> {
+        if self.version != Self::VERSION {
+            anyhow::bail!("snapshot version {} is not supported, expected {}", self.version, Self::VERSION);
+        }
+        self.params.check()?;
+        if self.params.boundary as usize >= BOUNDARY_NAMES.len() {
+            anyhow::bail!("unknown boundary mode {}, expected less than {}", self.params.boundary, BOUNDARY_NAMES.len());
+        }
+        let count = self.species.count;
+        if count == 0 || count as usize > SpeciesMatrix::MAX {
+            anyhow::bail!("expected 1 to {} species, got {}", SpeciesMatrix::MAX, count);
+        }
+        if self.species_ids.len() != self.particles.len() {
+            anyhow::bail!("{} species ids for {} particles", self.species_ids.len(), self.particles.len());
+        }
+        if let Some(id) = self.species_ids.iter().find(|&&id| id >= count) {
+            anyhow::bail!("species id {} is out of range for {} species", id, count);
+        }
+        Ok(())
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Self::MAGIC.to_vec();
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&self.frame.to_le_bytes());
+        bytes.extend(le_words(bytemuck::bytes_of(&self.params)));
+        bytes.extend(le_words(bytemuck::bytes_of(&self.species)));
+        bytes.extend_from_slice(&(self.particles.len() as u32).to_le_bytes());
+        bytes.extend(le_words(bytemuck::cast_slice(&self.particles)));
+        bytes.extend(le_words(bytemuck::cast_slice(&self.species_ids)));
+        bytes
+    }
+
+    fn from_bytes(mut bytes: &[u8]) -> anyhow::Result<Self> {
+        fn take<'a>(bytes: &mut &'a [u8], len: usize) -> anyhow::Result<&'a [u8]> {
+            if bytes.len() < len {
+                anyhow::bail!("snapshot is truncated");
+            }
+            let (head, tail) = bytes.split_at(len);
+            *bytes = tail;
+            Ok(head)
+        }
+
+        if take(&mut bytes, Self::MAGIC.len())? != Self::MAGIC {
+            anyhow::bail!("not a boids snapshot");
+        }
+        let version = u32::from_le_bytes(take(&mut bytes, 4)?.try_into()?);
+        if version != Self::VERSION {
+            anyhow::bail!("snapshot version {} is not supported, expected {}", version, Self::VERSION);
+        }
+        let seed = u64::from_le_bytes(take(&mut bytes, 8)?.try_into()?);
+        let frame = u64::from_le_bytes(take(&mut bytes, 8)?.try_into()?);
+        let params = bytemuck::pod_read_unaligned(&le_words(take(&mut bytes, mem::size_of::<SimParams>())?));
+        let species = bytemuck::pod_read_unaligned(&le_words(take(&mut bytes, mem::size_of::<SpeciesMatrix>())?));
+        let num_particles = u32::from_le_bytes(take(&mut bytes, 4)?.try_into()?) as usize;
+        let particles = le_words(take(&mut bytes, num_particles * PARTICLE_SIZE as usize)?)
+            .chunks_exact(PARTICLE_SIZE as usize)
+            .map(bytemuck::pod_read_unaligned)
+            .collect();
+        let species_ids = take(&mut bytes, num_particles * mem::size_of::<u32>())?
+            .chunks_exact(mem::size_of::<u32>())
+            .map(|id| u32::from_le_bytes(id.try_into().unwrap()))
+            .collect();
+        if !bytes.is_empty() {
+            anyhow::bail!("snapshot has {} bytes after the species ids", bytes.len());
+        }
+        Ok(Self { version, seed, frame, params, species, species_ids, particles })
+    }
+}
+
+/// `bytes`, a run of 32-bit words, with each word swapped between native and little-endian
+/// byte order, which is the same in both directions.
+fn le_words(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .chunks_exact(4)
+        .flat_map(|word| u32::from_ne_bytes(word.try_into().unwrap()).to_le_bytes())
+        .collect()
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
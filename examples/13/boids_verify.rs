@@ -0,0 +1,127 @@
+#![allow(dead_code)]
+mod boids_sim;
+#[path="../common/compute.rs"]
+mod compute;
+#[path="../common/reflect.rs"]
+mod reflect;
+#[path="../common/transforms.rs"]
+mod transforms;
+#[path="../common/validate.rs"]
+mod validate;
+
+use boids_sim::Particle;
+
+/// Largest per-particle position and velocity difference. Positions are compared on the
+/// [-1, 1] torus so a particle that wrapped on one side only does not count as diverged.
+fn divergence(a: &[Particle], b: &[Particle]) -> (f32, f32) {
+    let wrapped = |d: f32| d.min(2.0 - d);
+    a.iter().zip(b).fold((0.0f32, 0.0f32), |(max_pos, max_vel), (pa, pb)| {
+        let dx = wrapped((pa[0][0] - pb[0][0]).abs());
+        let dy = wrapped((pa[0][1] - pb[0][1]).abs());
+        let dvx = pa[1][0] - pb[1][0];
+        let dvy = pa[1][1] - pb[1][1];
+        (
+            max_pos.max((dx * dx + dy * dy).sqrt()),
+            max_vel.max((dvx * dvx + dvy * dvy).sqrt()),
+        )
+    })
+}
+
+/// What the simulation is run with, from the boids flags.
+#[derive(Default)]
+struct Scene {
+    params: boids_sim::SimParams,
+    obstacles: boids_sim::Obstacles,
+    species: boids_sim::SpeciesConfig,
+}
+
+async fn run(steps: usize, num_particles: u32, seed: u64, tolerance: f32, grid: bool, scene: Scene) -> anyhow::Result<()> {
+    let params = scene.params;
+    // the grid passes bind more storage buffers than the downlevel limits allow
+    let wgpu_config = transforms::WgpuConfig {
+        limits: if grid { wgpu::Limits::default() } else { wgpu::Limits::downlevel_defaults() },
+        ..transforms::WgpuConfig::from_env()
+    };
+    let instance = wgpu::Instance::new(wgpu_config.backends);
+    let adapter = wgpu_config.request_adapter(&instance, None).await?;
+    let (device, queue) = wgpu_config.request_device(&adapter).await?;
+
+    let mut cpu_particles = boids_sim::initial_particles(num_particles, seed);
+    let mut cpu_next = cpu_particles.clone();
+    let mut sim = boids_sim::Simulation::new(
+        &device,
+        &queue,
+        params,
+        &cpu_particles,
+        boids_sim::Simulation::DEFAULT_WORKGROUP_SIZE,
+    );
+    if grid {
+        sim.enable_grid(&device);
+    }
+    sim.set_obstacles(&queue, scene.obstacles);
+    sim.set_species(&queue, scene.species.matrix()?, scene.species.assign(num_particles));
+
+    let mut max_pos = 0.0f32;
+    let mut max_vel = 0.0f32;
+    println!("step,position,velocity");
+    for step in 1..=steps {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        sim.step(&mut encoder);
+        queue.submit(Some(encoder.finish()));
+
+        boids_sim::cpu_step(
+            &params,
+            &sim.attractors,
+            &sim.obstacles,
+            sim.sdf.as_ref(),
+            &sim.species,
+            &sim.species_ids,
+            &cpu_particles,
+            &mut cpu_next,
+        );
+        std::mem::swap(&mut cpu_particles, &mut cpu_next);
+
+        let gpu_particles = sim.read_particles(&device, &queue).await?;
+        let (pos, vel) = divergence(&cpu_particles, &gpu_particles);
+        println!("{},{},{}", step, pos, vel);
+        max_pos = max_pos.max(pos);
+        max_vel = max_vel.max(vel);
+    }
+
+    println!("max position divergence = {}, max velocity divergence = {}", max_pos, max_vel);
+    if max_pos > tolerance || max_vel > tolerance {
+        anyhow::bail!("GPU step diverged from the CPU reference by more than {}", tolerance);
+    }
+    Ok(())
+}
+
+/// Usage: boids_verify [--grid] [--boundary MODE] [--obstacle SPEC]... [--species FILE.json] [STEPS] [NUM_PARTICLES] [SEED] [TOLERANCE]
+///
+/// With `--grid` the binned neighbor search is checked instead of the O(n^2) `cs_main`.
+/// The other flags are the ones boids takes, see boids.rs.
+fn main() -> anyhow::Result<()> {
+    let mut grid = false;
+    let mut scene = Scene::default();
+    let mut args = Vec::new();
+    let mut iter = std::env::args();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--grid" => grid = true,
+            "--species" => scene.species = boids_sim::SpeciesConfig::load(iter.next().unwrap_or_default())?,
+            "--obstacle" => scene.obstacles.push(boids_sim::Obstacle::parse(&iter.next().unwrap_or_default())?)?,
+            flag if flag.starts_with("--") => {
+                if !scene.params.set_flag(flag, &iter.next().unwrap_or_default())? {
+                    anyhow::bail!("unknown flag {}", flag);
+                }
+            }
+            _ => args.push(arg),
+        }
+    }
+    let steps = args.get(1).map(|s| s.parse()).transpose()?.unwrap_or(50);
+    let num_particles = args.get(2).map(|s| s.parse()).transpose()?.unwrap_or(1000);
+    let seed = args.get(3).map(|s| s.parse()).transpose()?.unwrap_or(42);
+    let tolerance = args.get(4).map(|s| s.parse()).transpose()?.unwrap_or(1e-3);
+
+    env_logger::init();
+    pollster::block_on(run(steps, num_particles, seed, tolerance, grid, scene))
+}
@@ -0,0 +1,208 @@
+#![allow(dead_code)]
+use std::{
+    fs::File,
+    future::Future,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use crate::boids_sim::{Particle, PARTICLE_SIZE};
+
+type MapFuture = Pin<Box<dyn Future<Output = Result<(), wgpu::BufferAsyncError>> + Send>>;
+
+enum Slot {
+    Free,
+    /// Copy recorded in an encoder that has not been submitted yet.
+    Copied(usize),
+    Mapping(usize, MapFuture),
+}
+
+enum Output {
+    /// `frame,particle,species,x,y,vx,vy` rows.
+    Csv(BufWriter<File>),
+    /// A float32 array of shape `(frames, particles, 4)` holding `x, y, vx, vy`. The header is
+    /// rewritten with the final frame count by `finish`.
+    Npy(BufWriter<File>, usize),
+}
+
+/// Total size of the `.npy` preamble, so the header can be rewritten in place.
+const NPY_HEADER_SIZE: usize = 128;
+
+fn npy_header(frames: usize, num_particles: u32) -> Vec<u8> {
+    let dict = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}, 4), }}",
+        frames, num_particles
+    );
+    let mut header = b"\x93NUMPY\x01\x00".to_vec();
+    header.extend_from_slice(&((NPY_HEADER_SIZE - 10) as u16).to_le_bytes());
+    header.extend_from_slice(dict.as_bytes());
+    header.resize(NPY_HEADER_SIZE - 1, b' ');
+    header.push(b'\n');
+    header
+}
+
+/// Streams particle positions and velocities to a CSV or `.npy` file every `every` frames.
+///
+/// Captures are copied into a ring of staging buffers and written out once their mapping has
+/// completed, which `poll` checks without blocking, so rendering only waits when every staging
+/// buffer is still in flight.
+pub struct Recorder {
+    every: usize,
+    num_particles: u32,
+    species_ids: Vec<u32>,
+    buffers: Vec<wgpu::Buffer>,
+    slots: Vec<Slot>,
+    /// Ring position of the next capture; the oldest in flight is the first non-free slot from here.
+    next: usize,
+    output: Output,
+}
+
+impl Recorder {
+    pub const RING_SIZE: usize = 4;
+
+    /// Writes CSV for `.csv` paths and NumPy arrays for `.npy`.
+    pub fn new(
+        device: &wgpu::Device,
+        path: impl AsRef<Path>,
+        every: usize,
+        num_particles: u32,
+        species_ids: Vec<u32>,
+    ) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default().to_lowercase();
+        let mut file = BufWriter::new(File::create(path).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?);
+        let output = match extension.as_str() {
+            "csv" => {
+                writeln!(file, "frame,particle,species,x,y,vx,vy")?;
+                Output::Csv(file)
+            }
+            "npy" => {
+                file.write_all(&npy_header(0, num_particles))?;
+                Output::Npy(file, 0)
+            }
+            _ => anyhow::bail!("{}: trajectories are written as .csv or .npy", path.display()),
+        };
+        let buffers = (0..Self::RING_SIZE)
+            .map(|i| device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&format!("Trajectory Staging Buffer {}", i)),
+                size: num_particles as u64 * PARTICLE_SIZE,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }))
+            .collect();
+        Ok(Self {
+            every: every.max(1),
+            num_particles,
+            species_ids,
+            buffers,
+            slots: (0..Self::RING_SIZE).map(|_| Slot::Free).collect(),
+            next: 0,
+            output,
+        })
+    }
+
+    pub fn num_particles(&self) -> u32 {
+        self.num_particles
+    }
+
+    /// Records a copy of `particles`, the buffer the step that produced `frame` wrote, if
+    /// `frame` is one to keep. Call `submitted` once the encoder has been submitted.
+    pub fn capture(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        particles: &wgpu::Buffer,
+        frame: usize,
+    ) -> anyhow::Result<()> {
+        if frame % self.every != 0 {
+            return Ok(());
+        }
+        if !matches!(self.slots[self.next], Slot::Free) {
+            log::warn!("all {} trajectory staging buffers are in flight, waiting", Self::RING_SIZE);
+            self.drain(device, wgpu::Maintain::Wait, Some(self.next))?;
+        }
+        let size = self.num_particles as u64 * PARTICLE_SIZE;
+        encoder.copy_buffer_to_buffer(particles, 0, &self.buffers[self.next], 0, size);
+        self.slots[self.next] = Slot::Copied(frame);
+        self.next = (self.next + 1) % Self::RING_SIZE;
+        Ok(())
+    }
+
+    /// Starts mapping the copies recorded since the last call.
+    pub fn submitted(&mut self) {
+        for (slot, buffer) in self.slots.iter_mut().zip(self.buffers.iter()) {
+            if let Slot::Copied(frame) = *slot {
+                *slot = Slot::Mapping(frame, Box::pin(buffer.slice(..).map_async(wgpu::MapMode::Read)));
+            }
+        }
+    }
+
+    /// Writes out every capture whose mapping has completed, oldest first, without blocking.
+    pub fn poll(&mut self, device: &wgpu::Device) -> anyhow::Result<()> {
+        self.drain(device, wgpu::Maintain::Poll, None)
+    }
+
+    /// Waits for the captures still in flight, writes them and completes the file.
+    pub fn finish(mut self, device: &wgpu::Device) -> anyhow::Result<()> {
+        self.submitted();
+        self.drain(device, wgpu::Maintain::Wait, None)?;
+        match &mut self.output {
+            Output::Csv(file) => file.flush()?,
+            Output::Npy(file, frames) => {
+                file.seek(SeekFrom::Start(0))?;
+                file.write_all(&npy_header(*frames, self.num_particles))?;
+                file.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes completed captures in ring order starting from the oldest. With `Maintain::Wait`
+    /// it blocks until the slot `until` (or every slot) is done; otherwise it stops at the first
+    /// capture that is not ready yet, so frames stay in order.
+    fn drain(&mut self, device: &wgpu::Device, maintain: wgpu::Maintain, until: Option<usize>) -> anyhow::Result<()> {
+        device.poll(maintain);
+        let mut context = Context::from_waker(futures::task::noop_waker_ref());
+        for offset in 0..Self::RING_SIZE {
+            let i = (self.next + offset) % Self::RING_SIZE;
+            let frame = match &mut self.slots[i] {
+                Slot::Free => continue,
+                Slot::Copied(_) => break,
+                Slot::Mapping(frame, future) => match future.as_mut().poll(&mut context) {
+                    Poll::Ready(result) => {
+                        result?;
+                        *frame
+                    }
+                    Poll::Pending => break,
+                },
+            };
+            let data = self.buffers[i].slice(..).get_mapped_range();
+            let particles: Vec<Particle> = bytemuck::cast_slice(&data).to_vec();
+            drop(data);
+            self.buffers[i].unmap();
+            self.slots[i] = Slot::Free;
+            self.write(frame, &particles)?;
+            if until == Some(i) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, frame: usize, particles: &[Particle]) -> anyhow::Result<()> {
+        match &mut self.output {
+            Output::Csv(file) => {
+                for (i, [pos, vel]) in particles.iter().enumerate() {
+                    let species = self.species_ids.get(i).copied().unwrap_or_default();
+                    writeln!(file, "{},{},{},{},{},{},{}", frame, i, species, pos[0], pos[1], vel[0], vel[1])?;
+                }
+            }
+            Output::Npy(file, frames) => {
+                file.write_all(bytemuck::cast_slice(particles))?;
+                *frames += 1;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+#![allow(dead_code)]
+use cgmath::{Matrix4, Point3, SquareMatrix, Vector3};
+
+#[path="../common/compute.rs"]
+mod compute;
+#[path="../common/reflect.rs"]
+mod reflect;
+#[path="../common/transforms.rs"]
+mod transforms;
+#[path="../common/validate.rs"]
+mod validate;
+
+struct Transformed {
+    world: Vec<[f32; 3]>,
+    clip: Option<Vec<[f32; 4]>>,
+}
+
+impl Transformed {
+    /// Perspective-divided clip positions, if they were requested.
+    fn ndc(&self) -> Option<Vec<[f32; 3]>> {
+        self.clip
+            .as_ref()
+            .map(|clip| clip.iter().map(|p| [p[0] / p[3], p[1] / p[3], p[2] / p[3]]).collect())
+    }
+}
+
+/// Applies `model` to every point and, when `view_project` is given, also returns clip-space positions.
+async fn run(
+    points: &[[f32; 3]],
+    model: Matrix4<f32>,
+    view_project: Option<Matrix4<f32>>,
+) -> anyhow::Result<Transformed> {
+    let wgpu_config = transforms::WgpuConfig {
+        limits: wgpu::Limits::downlevel_defaults(),
+        ..transforms::WgpuConfig::from_env()
+    };
+    let instance = wgpu::Instance::new(wgpu_config.backends);
+    let adapter = wgpu_config.request_adapter(&instance, None).await?;
+    let (device, queue) = wgpu_config.request_device(&adapter).await?;
+
+    // vec3 arrays have a 16 byte stride in storage buffers, so upload homogeneous points
+    let input = points.iter().map(|p| [p[0], p[1], p[2], 1.0]).collect::<Vec<_>>();
+    let project = view_project.is_some();
+    let matrices: [[[f32; 4]; 4]; 2] = [
+        model.into(),
+        view_project.unwrap_or_else(Matrix4::identity).into(),
+    ];
+
+    let entry_point = if project { "project_points" } else { "transform_points" };
+    let mut job = compute::ComputeJob::new("transform3d.wgsl", include_str!("transform3d.wgsl"), entry_point)
+        .input(&input)
+        .uniform(&matrices)
+        .output::<[f32; 4]>(points.len());
+    if project {
+        job = job.output::<[f32; 4]>(points.len());
+    }
+    let outputs = job
+        .dispatch(points.len() as u32)
+        .run(&device, &queue)
+        .await?;
+
+    let world = outputs.get::<[f32; 4]>(0).iter().map(|p| [p[0], p[1], p[2]]).collect();
+    let clip = if project { Some(outputs.get::<[f32; 4]>(1)) } else { None };
+    Ok(Transformed { world, clip })
+}
+
+fn parse_vec3(text: &str) -> anyhow::Result<[f32; 3]> {
+    let xyz = text.split(',').map(|s| s.trim().parse::<f32>()).collect::<Result<Vec<_>, _>>();
+    match xyz.as_deref() {
+        Ok([x, y, z]) => Ok([*x, *y, *z]),
+        _ => Err(anyhow::anyhow!("expected \"x,y,z\", got {:?}", text)),
+    }
+}
+
+/// Parses `x,y,z` triples, one per line or separated by `;`. Blank lines and `#` comments are skipped.
+fn parse_points(text: &str) -> anyhow::Result<Vec<[f32; 3]>> {
+    text.split(['\n', ';'])
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(parse_vec3)
+        .collect()
+}
+
+/// Usage: transform3d [POINTS] [--translate x,y,z] [--rotate x,y,z] [--scale x,y,z] [--project ASPECT]
+///
+/// POINTS is `x,y,z[;x,y,z...]`, `-` to read CSV from stdin or `@file.csv`. Rotation is in radians.
+/// With `--project` the clip-space and NDC positions seen from a default camera are printed as well.
+fn main() -> anyhow::Result<()> {
+    let mut points = "1.0,0.0,0.0";
+    let mut translation = [0.0, 0.0, 0.0];
+    let mut rotation = [0.0, 0.0, 0.0];
+    let mut scaling = [1.0, 1.0, 1.0];
+    let mut aspect = None;
+
+    let args: Vec<String> = std::env::args().collect();
+    let mut i = 1;
+    while i < args.len() {
+        let value = args.get(i + 1).map(String::as_str).unwrap_or_default();
+        match args[i].as_str() {
+            "--translate" => translation = parse_vec3(value)?,
+            "--rotate" => rotation = parse_vec3(value)?,
+            "--scale" => scaling = parse_vec3(value)?,
+            "--project" => aspect = Some(value.parse::<f32>()?),
+            arg => {
+                points = arg;
+                i += 1;
+                continue;
+            }
+        }
+        i += 2;
+    }
+
+    let text = if points == "-" {
+        std::io::read_to_string(std::io::stdin())?
+    } else if let Some(path) = points.strip_prefix('@') {
+        std::fs::read_to_string(path)?
+    } else {
+        points.to_string()
+    };
+    let pts = parse_points(&text)?;
+
+    let model = transforms::create_transforms(translation, rotation, scaling);
+    let view_project = aspect.map(|aspect| {
+        let view = transforms::create_view(
+            Point3::new(3.0, 1.5, 3.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::unit_y(),
+        );
+        transforms::create_projection(aspect, true) * view
+    });
+
+    env_logger::init();
+    if pts.is_empty() {
+        return Ok(());
+    }
+    let transformed = pollster::block_on(run(&pts, model, view_project))?;
+    match (&transformed.clip, transformed.ndc()) {
+        (Some(clip), Some(ndc)) => {
+            for ((p, c), n) in transformed.world.iter().zip(clip).zip(ndc) {
+                println!("{},{},{},{},{},{},{},{},{},{}", p[0], p[1], p[2], c[0], c[1], c[2], c[3], n[0], n[1], n[2]);
+            }
+        }
+        _ => {
+            for p in transformed.world.iter() {
+                println!("{},{},{}", p[0], p[1], p[2]);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,35 @@
+#define WORKGROUP_SIZE 64
+
+[[block]] struct Points {
+  points: [[stride(16)]] array<vec4<f32>>;
+};
+
+[[block]] struct Transforms {
+  model: mat4x4<f32>;
+  view_project: mat4x4<f32>;
+};
+
+[[binding(0), group(0)]] var<storage, read> point_data : Points;
+[[binding(1), group(0)]] var<uniform> transforms : Transforms;
+[[binding(2), group(0)]] var<storage, read_write> world : Points;
+[[binding(3), group(0)]] var<storage, read_write> clip : Points;
+
+[[stage(compute), workgroup_size(WORKGROUP_SIZE)]]
+fn transform_points([[builtin(global_invocation_id)]] global_id : vec3<u32>) {
+  var index:u32 = global_id.x;
+  if (index >= arrayLength(&point_data.points)) {
+    return;
+  }
+  world.points[index] = transforms.model * vec4<f32>(point_data.points[index].xyz, 1.0);
+}
+
+[[stage(compute), workgroup_size(WORKGROUP_SIZE)]]
+fn project_points([[builtin(global_invocation_id)]] global_id : vec3<u32>) {
+  var index:u32 = global_id.x;
+  if (index >= arrayLength(&point_data.points)) {
+    return;
+  }
+  var pos:vec4<f32> = transforms.model * vec4<f32>(point_data.points[index].xyz, 1.0);
+  world.points[index] = pos;
+  clip.points[index] = transforms.view_project * pos;
+}
@@ -0,0 +1,378 @@
+#![allow(dead_code)]
+use cgmath::*;
+use winit::{dpi::PhysicalSize, event::*};
+use crate::transforms::{self, Projection};
+
+/// Fraction of the remaining way to cover in `dt` seconds, so smoothed motion looks the same
+/// at any frame rate: after one second `exp(-sharpness)` of the way is left.
+pub fn smoothing(sharpness: f32, dt: f32) -> f32 {
+    if sharpness.is_infinite() {
+        return 1.0;
+    }
+    1.0 - (-sharpness * dt).exp()
+}
+
+fn scroll_lines(delta: &MouseScrollDelta) -> f32 {
+    match delta {
+        MouseScrollDelta::LineDelta(_, y) => *y,
+        MouseScrollDelta::PixelDelta(p) => p.y as f32 / 50.0,
+    }
+}
+
+/// A camera driven by winit events. Input moves where the camera is headed and `update` eases
+/// the camera there.
+pub trait CameraController {
+    /// Returns true when the event was used.
+    fn window_event(&mut self, event: &WindowEvent) -> bool;
+
+    /// Raw mouse motion, which unlike `CursorMoved` keeps coming at the edge of the window.
+    fn device_event(&mut self, _event: &DeviceEvent) -> bool {
+        false
+    }
+
+    /// Moves the camera `dt` seconds further towards where the input has sent it.
+    fn update(&mut self, dt: f32);
+
+    fn view(&self) -> Matrix4<f32>;
+
+    fn projection(&self) -> Projection;
+
+    fn view_project(&self, aspect: f32) -> Matrix4<f32> {
+        self.projection().matrix(aspect) * self.view()
+    }
+}
+
+/// Where an `OrbitController` is, or is headed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Orbit {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    /// Radians around the y axis, 0 looking down -z.
+    pub yaw: f32,
+    /// Radians above the target's horizontal plane.
+    pub pitch: f32,
+}
+
+impl Orbit {
+    pub fn eye(&self) -> Point3<f32> {
+        self.target + self.distance * Vector3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        )
+    }
+
+    fn approach(&mut self, goal: &Orbit, amount: f32) {
+        self.target += (goal.target - self.target) * amount;
+        // zoom evenly in log space, so the approach does not speed up close to the target
+        self.distance *= (goal.distance / self.distance).powf(amount);
+        self.yaw += (goal.yaw - self.yaw) * amount;
+        self.pitch += (goal.pitch - self.pitch) * amount;
+    }
+}
+
+/// Circles a target: left drag turns around it and the wheel zooms in and out.
+pub struct OrbitController {
+    pub orbit: Orbit,
+    pub goal: Orbit,
+    pub projection: Projection,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Radians per pixel of mouse drag.
+    pub sensitivity: f32,
+    /// See `smoothing`; `f32::INFINITY` turns smoothing off.
+    pub sharpness: f32,
+    dragging: bool,
+    cursor: Option<[f32; 2]>,
+}
+
+impl OrbitController {
+    const MAX_PITCH: f32 = 1.5;
+
+    pub fn new(target: Point3<f32>, distance: f32, yaw: f32, pitch: f32) -> Self {
+        let orbit = Orbit { target, distance, yaw, pitch };
+        Self {
+            orbit,
+            goal: orbit,
+            projection: Projection::default(),
+            min_distance: 0.5,
+            max_distance: 20.0,
+            sensitivity: 0.005,
+            sharpness: 15.0,
+            dragging: false,
+            cursor: None,
+        }
+    }
+
+    pub fn eye(&self) -> Point3<f32> {
+        self.orbit.eye()
+    }
+}
+
+impl CameraController for OrbitController {
+    fn window_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+                self.dragging = *state == ElementState::Pressed;
+                false
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let cursor = [position.x as f32, position.y as f32];
+                match self.cursor.replace(cursor) {
+                    Some(last) if self.dragging => {
+                        self.goal.yaw -= (cursor[0] - last[0]) * self.sensitivity;
+                        self.goal.pitch = (self.goal.pitch + (cursor[1] - last[1]) * self.sensitivity)
+                            .clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.goal.distance = (self.goal.distance * 0.9f32.powf(scroll_lines(delta)))
+                    .clamp(self.min_distance, self.max_distance);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn update(&mut self, dt: f32) {
+        let goal = self.goal;
+        self.orbit.approach(&goal, smoothing(self.sharpness, dt));
+    }
+
+    fn view(&self) -> Matrix4<f32> {
+        transforms::create_view(self.orbit.eye(), self.orbit.target, Vector3::unit_y())
+    }
+
+    fn projection(&self) -> Projection {
+        self.projection
+    }
+}
+
+/// First-person flight: W/A/S/D move, E and Q/Shift rise and sink, and moving the mouse
+/// with the right button held looks around.
+pub struct FlyController {
+    pub position: Point3<f32>,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub goal_position: Point3<f32>,
+    pub goal_yaw: f32,
+    pub goal_pitch: f32,
+    pub projection: Projection,
+    /// Units per second.
+    pub speed: f32,
+    /// Radians per unit of raw mouse motion.
+    pub sensitivity: f32,
+    /// See `smoothing`; `f32::INFINITY` turns smoothing off.
+    pub sharpness: f32,
+    /// Which of the keys for backward/forward, left/right and down/up are held.
+    movement: [[bool; 2]; 3],
+    looking: bool,
+}
+
+impl FlyController {
+    const MAX_PITCH: f32 = 1.5;
+
+    /// Starts at `position` looking at `target`.
+    pub fn new(position: Point3<f32>, target: Point3<f32>) -> Self {
+        let d = target - position;
+        let yaw = (-d.x).atan2(-d.z);
+        let pitch = d.y.atan2((d.x * d.x + d.z * d.z).sqrt()).clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+        Self {
+            position,
+            yaw,
+            pitch,
+            goal_position: position,
+            goal_yaw: yaw,
+            goal_pitch: pitch,
+            projection: Projection::default(),
+            speed: 2.0,
+            sensitivity: 0.003,
+            sharpness: 15.0,
+            movement: [[false; 2]; 3],
+            looking: false,
+        }
+    }
+
+    pub fn forward(&self) -> Vector3<f32> {
+        Vector3::new(
+            -self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            -self.pitch.cos() * self.yaw.cos(),
+        )
+    }
+
+    pub fn right(&self) -> Vector3<f32> {
+        Vector3::new(self.yaw.cos(), 0.0, -self.yaw.sin())
+    }
+}
+
+impl CameraController for FlyController {
+    fn window_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    state,
+                    virtual_keycode: Some(key),
+                    ..
+                },
+                ..
+            } => {
+                let (axis, positive) = match key {
+                    VirtualKeyCode::W => (0, true),
+                    VirtualKeyCode::S => (0, false),
+                    VirtualKeyCode::D => (1, true),
+                    VirtualKeyCode::A => (1, false),
+                    VirtualKeyCode::E => (2, true),
+                    VirtualKeyCode::Q | VirtualKeyCode::LShift => (2, false),
+                    _ => return false,
+                };
+                self.movement[axis][positive as usize] = *state == ElementState::Pressed;
+                true
+            }
+            WindowEvent::MouseInput { state, button: MouseButton::Right, .. } => {
+                self.looking = *state == ElementState::Pressed;
+                true
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.speed *= 1.25f32.powf(scroll_lines(delta));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn device_event(&mut self, event: &DeviceEvent) -> bool {
+        match event {
+            DeviceEvent::MouseMotion { delta: (dx, dy) } if self.looking => {
+                self.goal_yaw -= *dx as f32 * self.sensitivity;
+                self.goal_pitch = (self.goal_pitch - *dy as f32 * self.sensitivity)
+                    .clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn update(&mut self, dt: f32) {
+        let [forward, right, up] = self.movement.map(|[negative, positive]| positive as i32 - negative as i32);
+        let direction = self.forward() * forward as f32 + self.right() * right as f32 + Vector3::unit_y() * up as f32;
+        if direction.magnitude2() > 0.0 {
+            self.goal_position += direction.normalize() * self.speed * dt;
+        }
+        let amount = smoothing(self.sharpness, dt);
+        self.position += (self.goal_position - self.position) * amount;
+        self.yaw += (self.goal_yaw - self.yaw) * amount;
+        self.pitch += (self.goal_pitch - self.pitch) * amount;
+    }
+
+    fn view(&self) -> Matrix4<f32> {
+        transforms::create_view(self.position, self.position + self.forward(), Vector3::unit_y())
+    }
+
+    fn projection(&self) -> Projection {
+        self.projection
+    }
+}
+
+/// A 2D view of the xy plane: left drag pans and the wheel zooms around the cursor. At zoom 1
+/// the view spans y from -1 to 1 around `center`.
+pub struct PanZoomController {
+    pub center: Vector2<f32>,
+    pub zoom: f32,
+    pub goal_center: Vector2<f32>,
+    pub goal_zoom: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    /// See `smoothing`; `f32::INFINITY` turns smoothing off.
+    pub sharpness: f32,
+    size: PhysicalSize<u32>,
+    dragging: bool,
+    cursor: Option<[f32; 2]>,
+}
+
+impl PanZoomController {
+    /// `size` is the window's inner size; later `Resized` events keep it current.
+    pub fn new(size: PhysicalSize<u32>) -> Self {
+        Self {
+            center: Vector2::zero(),
+            zoom: 1.0,
+            goal_center: Vector2::zero(),
+            goal_zoom: 1.0,
+            min_zoom: 0.1,
+            max_zoom: 100.0,
+            sharpness: 15.0,
+            size,
+            dragging: false,
+            cursor: None,
+        }
+    }
+
+    /// World units per pixel at the goal zoom.
+    fn pixel_size(&self) -> f32 {
+        2.0 / self.goal_zoom / self.size.height.max(1) as f32
+    }
+
+    /// The point of the plane under window position `cursor` once the camera has arrived.
+    pub fn world_position(&self, cursor: [f32; 2]) -> Vector2<f32> {
+        let pixel_size = self.pixel_size();
+        self.goal_center + Vector2::new(
+            (cursor[0] - 0.5 * self.size.width as f32) * pixel_size,
+            (0.5 * self.size.height as f32 - cursor[1]) * pixel_size,
+        )
+    }
+}
+
+impl CameraController for PanZoomController {
+    fn window_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::Resized(size) => {
+                self.size = *size;
+                false
+            }
+            WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+                self.dragging = *state == ElementState::Pressed;
+                false
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let cursor = [position.x as f32, position.y as f32];
+                match self.cursor.replace(cursor) {
+                    Some(last) if self.dragging => {
+                        let pixel_size = self.pixel_size();
+                        self.goal_center.x -= (cursor[0] - last[0]) * pixel_size;
+                        self.goal_center.y += (cursor[1] - last[1]) * pixel_size;
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let cursor = self.cursor.unwrap_or([0.5 * self.size.width as f32, 0.5 * self.size.height as f32]);
+                let anchor = self.world_position(cursor);
+                let zoom = (self.goal_zoom * 1.1f32.powf(scroll_lines(delta))).clamp(self.min_zoom, self.max_zoom);
+                // keep the point under the cursor where it is
+                self.goal_center = anchor - (anchor - self.goal_center) * (self.goal_zoom / zoom);
+                self.goal_zoom = zoom;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn update(&mut self, dt: f32) {
+        let amount = smoothing(self.sharpness, dt);
+        self.center += (self.goal_center - self.center) * amount;
+        self.zoom *= (self.goal_zoom / self.zoom).powf(amount);
+    }
+
+    fn view(&self) -> Matrix4<f32> {
+        let (x, y) = (self.center.x, self.center.y);
+        transforms::create_view(Point3::new(x, y, 1.0), Point3::new(x, y, 0.0), Vector3::unit_y())
+    }
+
+    fn projection(&self) -> Projection {
+        Projection::orthographic(2.0 / self.zoom, 0.0, 2.0)
+    }
+}
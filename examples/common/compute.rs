@@ -0,0 +1,244 @@
+#![allow(dead_code)]
+use std::mem;
+use bytemuck::Pod;
+use wgpu::util::DeviceExt;
+use crate::reflect::Reflection;
+use crate::validate::{self, preprocess::Preprocessor};
+
+/// WebGPU's `maxComputeWorkgroupsPerDimension`, which `wgpu::Limits` does not have yet.
+pub const MAX_WORKGROUPS_PER_DIMENSION: u32 = 65535;
+
+enum Binding {
+    Input(Vec<u8>),
+    Uniform(Vec<u8>),
+    Output(u64),
+}
+
+/// A single compute dispatch over group 0. Bindings are numbered in the order they are added,
+/// so `input`, `uniform` and `output` calls have to follow the `[[binding(n)]]`s in the WGSL;
+/// `run` checks them against the shader before dispatching.
+pub struct ComputeJob<'a> {
+    file: &'a str,
+    source: &'a str,
+    entry_point: &'a str,
+    bindings: Vec<Binding>,
+    invocations: u32,
+    /// Overrides the shader's own `WORKGROUP_SIZE` when set.
+    workgroup_size: Option<u32>,
+}
+
+/// Read back contents of the `output` bindings, in the order they were added.
+pub struct ComputeOutputs {
+    buffers: Vec<Vec<u8>>,
+}
+
+impl ComputeOutputs {
+    pub fn get<T: Pod>(&self, index: usize) -> Vec<T> {
+        self.buffers[index]
+            .chunks_exact(mem::size_of::<T>())
+            .map(bytemuck::pod_read_unaligned)
+            .collect()
+    }
+}
+
+impl<'a> ComputeJob<'a> {
+    /// `file` names the shader in validation errors.
+    pub fn new(file: &'a str, source: &'a str, entry_point: &'a str) -> Self {
+        Self {
+            file,
+            source,
+            entry_point,
+            bindings: Vec::new(),
+            invocations: 1,
+            workgroup_size: None,
+        }
+    }
+
+    /// Read-only storage buffer, `var<storage, read>` in the shader.
+    pub fn input<T: Pod>(mut self, data: &[T]) -> Self {
+        self.bindings.push(Binding::Input(bytemuck::cast_slice(data).to_vec()));
+        self
+    }
+
+    /// Uniform buffer, `var<uniform>` in the shader.
+    pub fn uniform<T: Pod>(mut self, value: &T) -> Self {
+        self.bindings.push(Binding::Uniform(bytemuck::bytes_of(value).to_vec()));
+        self
+    }
+
+    /// Read-write storage buffer holding `len` elements of `T` that is copied back after the dispatch.
+    pub fn output<T: Pod>(mut self, len: usize) -> Self {
+        self.bindings.push(Binding::Output((len * mem::size_of::<T>()) as u64));
+        self
+    }
+
+    /// Number of invocations to cover, in workgroups of the size the shader declares.
+    pub fn dispatch(mut self, invocations: u32) -> Self {
+        self.invocations = invocations;
+        self
+    }
+
+    /// Runs in workgroups of `size` instead, which the shader sees as `WORKGROUP_SIZE`.
+    pub fn workgroup_size(mut self, size: u32) -> Self {
+        self.workgroup_size = Some(size);
+        self
+    }
+
+    /// The workgroups `run` dispatches, or an error if the workgroup size is 0 or they do not
+    /// fit in one dispatch.
+    pub fn work_group_count(&self) -> anyhow::Result<u32> {
+        let workgroup_size = match self.workgroup_size {
+            Some(size) => size,
+            None => Reflection::new(self.file, self.source, &[self.entry_point])?.workgroup_size()?[0],
+        };
+        if workgroup_size == 0 {
+            anyhow::bail!("{}: the workgroup size must be at least 1", self.file);
+        }
+        let count = self.invocations.div_ceil(workgroup_size);
+        if count > MAX_WORKGROUPS_PER_DIMENSION {
+            anyhow::bail!(
+                "{}: {} invocations in workgroups of {} need {} workgroups, more than the {} of a dispatch",
+                self.file, self.invocations, workgroup_size, count, MAX_WORKGROUPS_PER_DIMENSION,
+            );
+        }
+        Ok(count)
+    }
+
+    /// The group 0 layout the shader's entry point declares, after checking that the added
+    /// bindings match it in number, kind and size.
+    pub fn layout_entries(&self) -> anyhow::Result<Vec<wgpu::BindGroupLayoutEntry>> {
+        let reflection = Reflection::new(self.file, self.source, &[self.entry_point])?;
+        let mut groups = reflection.bind_group_layout_entries()?;
+        if groups.len() > 1 {
+            anyhow::bail!("{} binds groups other than 0", self.file);
+        }
+        let layout_entries = groups.pop().unwrap_or_default();
+        if layout_entries.len() != self.bindings.len() {
+            anyhow::bail!("{} binds {} buffers, the job has {}", self.file, layout_entries.len(), self.bindings.len());
+        }
+        for (i, (entry, binding)) in layout_entries.iter().zip(&self.bindings).enumerate() {
+            let (ty, size) = match binding {
+                Binding::Input(data) => (wgpu::BufferBindingType::Storage {read_only: true}, data.len() as u64),
+                Binding::Uniform(data) => (wgpu::BufferBindingType::Uniform, data.len() as u64),
+                Binding::Output(size) => (wgpu::BufferBindingType::Storage {read_only: false}, *size),
+            };
+            if !matches!(entry.ty, wgpu::BindingType::Buffer { ty: declared, .. } if declared == ty) || entry.binding != i as u32 {
+                anyhow::bail!("{}: binding {} is {:?}, the job's binding {} is {:?}", self.file, entry.binding, entry.ty, i, ty);
+            }
+            reflection.check_buffer_size(0, entry.binding, size)?;
+        }
+        Ok(layout_entries)
+    }
+
+    pub async fn run(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<ComputeOutputs> {
+        let work_group_count = self.work_group_count()?;
+        let layout_entries = self.layout_entries()?;
+
+        let preprocessor = match self.workgroup_size {
+            Some(size) => Preprocessor::new().define("WORKGROUP_SIZE", size),
+            None => Preprocessor::new(),
+        };
+        let shader = validate::try_shader_module_with(device, &preprocessor, self.file, self.source)?;
+
+        let buffers = self
+            .bindings
+            .iter()
+            .enumerate()
+            .map(|(i, binding)| match binding {
+                Binding::Input(data) => device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("Input Buffer {}", i)),
+                    contents: data,
+                    usage: wgpu::BufferUsages::STORAGE,
+                }),
+                Binding::Uniform(data) => device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("Uniform Buffer {}", i)),
+                    contents: data,
+                    usage: wgpu::BufferUsages::UNIFORM,
+                }),
+                Binding::Output(size) => device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&format!("Output Buffer {}", i)),
+                    size: *size,
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                }),
+            })
+            .collect::<Vec<_>>();
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor{
+            entries: &layout_entries,
+            label: Some("Compute Bind Group Layout"),
+        });
+
+        let bind_group_entries = buffers
+            .iter()
+            .enumerate()
+            .map(|(i, buffer)| wgpu::BindGroupEntry {
+                binding: i as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect::<Vec<_>>();
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor{
+            layout: &bind_group_layout,
+            entries: &bind_group_entries,
+            label: Some("Compute Bind Group"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Compute Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Compute Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: self.entry_point,
+        });
+
+        let read_buffers = self
+            .bindings
+            .iter()
+            .zip(buffers.iter())
+            .filter_map(|(binding, buffer)| match binding {
+                Binding::Output(size) => Some((buffer, *size)),
+                _ => None,
+            })
+            .map(|(buffer, size)| {
+                let read_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Read Buffer"),
+                    size,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                (buffer, read_buffer, size)
+            })
+            .collect::<Vec<_>>();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+            compute_pass.set_pipeline(&pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            compute_pass.insert_debug_marker(self.entry_point);
+            compute_pass.dispatch(work_group_count, 1, 1);
+        }
+        for (buffer, read_buffer, size) in read_buffers.iter() {
+            encoder.copy_buffer_to_buffer(buffer, 0, read_buffer, 0, *size);
+        }
+        queue.submit(Some(encoder.finish()));
+
+        let mut outputs = Vec::with_capacity(read_buffers.len());
+        for (_, read_buffer, _) in read_buffers.iter() {
+            let read_buffer_slice = read_buffer.slice(..);
+            let read_buffer_future = read_buffer_slice.map_async(wgpu::MapMode::Read);
+            device.poll(wgpu::Maintain::Wait);
+            read_buffer_future.await?;
+            outputs.push(read_buffer_slice.get_mapped_range().to_vec());
+            read_buffer.unmap();
+        }
+
+        Ok(ComputeOutputs { buffers: outputs })
+    }
+}
@@ -0,0 +1,111 @@
+#![allow(dead_code)]
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use crate::validate::{self, preprocess::{self, Preprocessor}};
+
+/// The directory `#include` paths are relative to.
+const INCLUDE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/examples");
+
+/// How often a window waiting for input wakes up to look for shader changes.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Notices when any of a set of files changes on disk, by polling modification times.
+pub struct ShaderWatcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    preprocessor: Preprocessor,
+}
+
+impl ShaderWatcher {
+    pub fn new(paths: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        let files = paths
+            .into_iter()
+            .map(|path| {
+                let path = path.into();
+                let time = modified(&path);
+                (path, time)
+            })
+            .collect();
+        Self { files, preprocessor: Preprocessor::new() }
+    }
+
+    /// Watches `paths` and every snippet a shader can `#include`, which `preprocessor` then
+    /// reads from disk as well, so that editing one reloads the shaders including it.
+    pub fn with_includes(paths: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        let dir = Path::new(INCLUDE_DIR);
+        let includes = preprocess::INCLUDES.iter().map(|(path, _)| dir.join(path));
+        let watcher = Self::new(paths.into_iter().map(Into::into).chain(includes));
+        Self { preprocessor: Preprocessor::new().include_dir(dir), ..watcher }
+    }
+
+    /// What reloaded shaders are compiled with, reading includes from disk for `with_includes`.
+    pub fn preprocessor(&self) -> &Preprocessor {
+        &self.preprocessor
+    }
+
+    /// True when a file was modified, created or removed since the last call.
+    pub fn changed(&mut self) -> bool {
+        let mut changed = false;
+        for (path, time) in self.files.iter_mut() {
+            let now = modified(path);
+            if now != *time {
+                *time = now;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// Reads and validates a shader for hot reloading, with the error in the same form
+/// `ErrorCapture` gives, so a broken edit never reaches wgpu.
+pub fn read_source(preprocessor: &Preprocessor, path: &Path) -> Result<String, String> {
+    let source = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    validate::validate_with(preprocessor, &path.display().to_string(), &source).map_err(|e| e.to_string())?;
+    Ok(source)
+}
+
+/// Catches the validation errors wgpu reports while rebuilding pipelines from edited shaders.
+///
+/// wgpu 0.11 has no error scopes, so this replaces the device's uncaptured error handler. Errors
+/// raised inside `capture` are returned from it; any other error still panics, as it would
+/// with the default handler.
+pub struct ErrorCapture {
+    errors: Arc<Mutex<Option<Vec<String>>>>,
+}
+
+impl ErrorCapture {
+    pub fn install(device: &wgpu::Device) -> Self {
+        let errors: Arc<Mutex<Option<Vec<String>>>> = Arc::new(Mutex::new(None));
+        let sink = errors.clone();
+        device.on_uncaptured_error(move |error| {
+            let message = error.to_string();
+            if let Some(captured) = sink.lock().unwrap().as_mut() {
+                captured.push(message);
+                return;
+            }
+            log::error!("{}", message);
+            panic!("wgpu error: {}", message);
+        });
+        Self { errors }
+    }
+
+    /// Runs `create`, returning its result only if wgpu reported no errors meanwhile. wgpu
+    /// reports these synchronously, so the objects are known to be valid once this returns.
+    pub fn capture<T>(&self, create: impl FnOnce() -> T) -> Result<T, String> {
+        *self.errors.lock().unwrap() = Some(Vec::new());
+        let value = create();
+        let errors = self.errors.lock().unwrap().take().unwrap_or_default();
+        if errors.is_empty() {
+            Ok(value)
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+}
@@ -0,0 +1,9 @@
+// Shared by the shaders that `#include "common/math.wgsl"`; see common/preprocess.rs.
+
+let PI : f32 = 3.1415926;
+
+// v rotated counterclockwise by angle radians.
+fn rotate2d(v : vec2<f32>, angle : f32) -> vec2<f32> {
+  return vec2<f32>(v.x * cos(angle) - v.y * sin(angle),
+                   v.x * sin(angle) + v.y * cos(angle));
+}
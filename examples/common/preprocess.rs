@@ -0,0 +1,201 @@
+#![allow(dead_code)]
+use std::{
+    borrow::Cow,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Snippets shaders can `#include`, by their path under examples/.
+pub const INCLUDES: [(&str, &str); 2] = [
+    ("common/math.wgsl", include_str!("math.wgsl")),
+    ("13/boids_shared.wgsl", include_str!("../13/boids_shared.wgsl")),
+];
+
+/// The snippet `#include "path"` names: the copy compiled in, or with `include_dir` the file
+/// under it.
+fn snippet(path: &str, include_dir: Option<&Path>) -> Result<(&'static str, Cow<'static, str>), String> {
+    let (path, source) = INCLUDES
+        .iter()
+        .find(|(include, _)| *include == path)
+        .ok_or_else(|| format!("no shader snippet {:?} to include", path))?;
+    match include_dir {
+        Some(dir) => {
+            let file = dir.join(path);
+            let source = std::fs::read_to_string(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+            Ok((path, Cow::Owned(source)))
+        }
+        None => Ok((path, Cow::Borrowed(source))),
+    }
+}
+
+/// A malformed directive, at the line it is on.
+#[derive(Debug)]
+pub struct Error {
+    pub file: String,
+    /// 1-based, like `Preprocessed::origin`.
+    pub line: usize,
+    pub column: usize,
+    /// The line's contents.
+    pub text: String,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Resolves the directives WGSL lacks before a shader reaches naga or wgpu:
+///
+/// - `#include "common/math.wgsl"` pastes in a file from `INCLUDES`, at most once per shader
+///   since WGSL cannot declare anything twice.
+/// - `#define NAME value` replaces the identifier `NAME` with `value` on the lines after it.
+///
+/// Values given with `define` take precedence over the shader's own `#define`s, which then
+/// serve as defaults, so `[[workgroup_size(WORKGROUP_SIZE)]]` can follow the Rust side.
+#[derive(Clone, Debug, Default)]
+pub struct Preprocessor {
+    defines: Vec<(String, String)>,
+    include_dir: Option<PathBuf>,
+}
+
+/// WGSL with the directives resolved, and where each of its lines came from.
+pub struct Preprocessed {
+    pub source: String,
+    /// The file and 1-based line of each line of `source`.
+    lines: Vec<(String, usize)>,
+}
+
+impl Preprocessed {
+    /// The file and line that line `line` (1-based) of `source` came from.
+    pub fn origin(&self, line: usize) -> (&str, usize) {
+        match self.lines.get(line.wrapping_sub(1)) {
+            Some((file, line)) => (file, *line),
+            None => self.lines.last().map_or(("", line), |(file, last)| (file.as_str(), last + 1)),
+        }
+    }
+}
+
+impl Preprocessor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn define(mut self, name: &str, value: impl ToString) -> Self {
+        self.defines.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Reads included snippets from `dir`, the examples/ directory, instead of the copies
+    /// compiled in, so that hot reloading picks up edits to them. Only the paths in `INCLUDES`
+    /// can still be included.
+    pub fn include_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.include_dir = Some(dir.into());
+        self
+    }
+
+    pub fn run(&self, file: &str, source: &str) -> Result<Preprocessed, Error> {
+        let mut state = State {
+            include_dir: self.include_dir.as_deref(),
+            defines: self.defines.clone(),
+            caller_defines: self.defines.len(),
+            included: Vec::new(),
+            output: Preprocessed { source: String::with_capacity(source.len()), lines: Vec::new() },
+        };
+        state.process(file, source)?;
+        Ok(state.output)
+    }
+}
+
+struct State<'a> {
+    include_dir: Option<&'a Path>,
+    defines: Vec<(String, String)>,
+    /// How many of `defines` came from `Preprocessor::define`.
+    caller_defines: usize,
+    included: Vec<String>,
+    output: Preprocessed,
+}
+
+impl State<'_> {
+    fn process(&mut self, file: &str, source: &str) -> Result<(), Error> {
+        for (index, text) in source.lines().enumerate() {
+            let line = index + 1;
+            let error = |message: String| Error {
+                file: file.to_string(),
+                line,
+                column: text.len() - text.trim_start().len() + 1,
+                text: text.to_string(),
+                message,
+            };
+            let directive = text.trim_start();
+            if let Some(rest) = directive.strip_prefix("#include") {
+                let path = rest
+                    .trim()
+                    .strip_prefix('"')
+                    .and_then(|rest| rest.strip_suffix('"'))
+                    .ok_or_else(|| error("expected #include \"path\"".to_string()))?;
+                if !self.included.iter().any(|done| done == path) {
+                    let (path, included) = snippet(path, self.include_dir).map_err(error)?;
+                    self.included.push(path.to_string());
+                    self.process(path, &included)?;
+                }
+            } else if let Some(rest) = directive.strip_prefix("#define") {
+                let mut parts = rest.trim().splitn(2, char::is_whitespace);
+                let name = parts.next().filter(|name| is_identifier(name));
+                let value = parts.next().map(str::trim).filter(|value| !value.is_empty());
+                let (name, value) = name
+                    .zip(value)
+                    .ok_or_else(|| error("expected #define NAME value".to_string()))?;
+                match self.defines.iter().position(|(defined, _)| defined == name) {
+                    Some(i) if i < self.caller_defines => {}
+                    Some(i) => self.defines[i].1 = self.substitute(value),
+                    None => {
+                        let value = self.substitute(value);
+                        self.defines.push((name.to_string(), value));
+                    }
+                }
+            } else if directive.starts_with('#') {
+                return Err(error("unknown directive, expected #include or #define".to_string()));
+            } else {
+                let text = self.substitute(text);
+                self.output.source.push_str(&text);
+                self.output.source.push('\n');
+                self.output.lines.push((file.to_string(), line));
+            }
+        }
+        Ok(())
+    }
+
+    /// `text` with every identifier that names a define replaced by its value.
+    fn substitute(&self, text: &str) -> String {
+        if self.defines.is_empty() {
+            return text.to_string();
+        }
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            result.push_str(&rest[..start]);
+            // an identifier, or a number's suffix or exponent
+            let in_number = result.chars().next_back().is_some_and(|c| c.is_ascii_alphanumeric() || c == '.');
+            let end = rest[start..]
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .map_or(rest.len(), |i| start + i);
+            let word = &rest[start..end];
+            match self.defines.iter().find(|(name, _)| name == word) {
+                Some((_, value)) if !in_number => result.push_str(value),
+                _ => result.push_str(word),
+            }
+            rest = &rest[end..];
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
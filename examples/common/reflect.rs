@@ -0,0 +1,253 @@
+#![allow(dead_code)]
+use std::collections::BTreeMap;
+use anyhow::{anyhow, bail};
+use naga::{proc::Layouter, Handle};
+use crate::validate;
+
+/// The interface of one pipeline's entry points in a WGSL module: its bind group and vertex
+/// layouts, so the Rust side does not have to repeat what the `[[group(m), binding(n)]]` and
+/// `[[location(n)]]` declarations already say.
+///
+/// Only what the entry points use counts, so a file holding both render and compute stages
+/// may declare the same binding twice.
+pub struct Reflection {
+    file: String,
+    module: naga::Module,
+    layouter: Layouter,
+    entry_points: Vec<usize>,
+    /// By group and binding, with the stages that use each.
+    bindings: BTreeMap<(u32, u32), (Handle<naga::GlobalVariable>, wgpu::ShaderStages)>,
+}
+
+impl Reflection {
+    pub fn new(file: &str, source: &str, entry_points: &[&str]) -> anyhow::Result<Self> {
+        let (module, info) = validate::validate(file, source)?;
+        let mut layouter = Layouter::default();
+        // validation has already laid out every type
+        layouter.update(&module.types, &module.constants).unwrap();
+
+        let mut indices = Vec::with_capacity(entry_points.len());
+        let mut bindings = BTreeMap::new();
+        for name in entry_points {
+            let index = module
+                .entry_points
+                .iter()
+                .position(|entry_point| entry_point.name == *name)
+                .ok_or_else(|| anyhow!("{} has no entry point `{}`", file, name))?;
+            let stage = match module.entry_points[index].stage {
+                naga::ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+                naga::ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+                naga::ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+            };
+            let uses = info.get_entry_point(index);
+            for (handle, global) in module.global_variables.iter() {
+                if let (Some(binding), false) = (&global.binding, uses[handle].is_empty()) {
+                    bindings.entry((binding.group, binding.binding)).or_insert((handle, stage)).1 |= stage;
+                }
+            }
+            indices.push(index);
+        }
+        Ok(Self { file: file.to_string(), module, layouter, entry_points: indices, bindings })
+    }
+
+    /// The layout entries of every bind group, indexed by group; a group the entry points do
+    /// not use is left empty. Each binding is visible to the stages that use it.
+    ///
+    /// Float textures are filterable only when the entry points also use a sampler, since the
+    /// unfilterable formats, such as `R32Float`, can then still be bound for `textureLoad`.
+    pub fn bind_group_layout_entries(&self) -> anyhow::Result<Vec<Vec<wgpu::BindGroupLayoutEntry>>> {
+        let uses_sampler = self
+            .bindings
+            .values()
+            .any(|(handle, _)| matches!(self.module.types[self.module.global_variables[*handle].ty].inner, naga::TypeInner::Sampler { .. }));
+        let num_groups = self.bindings.keys().last().map_or(0, |(group, _)| group + 1);
+        let mut groups = vec![Vec::new(); num_groups as usize];
+        for (&(group, binding), &(handle, visibility)) in &self.bindings {
+            groups[group as usize].push(wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility,
+                ty: self.binding_type(handle, uses_sampler)?,
+                count: None,
+            });
+        }
+        Ok(groups)
+    }
+
+    fn binding_type(&self, handle: Handle<naga::GlobalVariable>, filterable: bool) -> anyhow::Result<wgpu::BindingType> {
+        let global = &self.module.global_variables[handle];
+        let buffer = |ty| wgpu::BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(self.layouter[global.ty].size as u64),
+        };
+        Ok(match global.class {
+            naga::StorageClass::Uniform => buffer(wgpu::BufferBindingType::Uniform),
+            naga::StorageClass::Storage { access } => buffer(wgpu::BufferBindingType::Storage {
+                read_only: !access.contains(naga::StorageAccess::STORE),
+            }),
+            _ => match self.module.types[global.ty].inner {
+                naga::TypeInner::Sampler { comparison } => wgpu::BindingType::Sampler { filtering: true, comparison },
+                naga::TypeInner::Image { dim, arrayed, class: naga::ImageClass::Sampled { kind, multi } } => {
+                    wgpu::BindingType::Texture {
+                        sample_type: match kind {
+                            naga::ScalarKind::Float => wgpu::TextureSampleType::Float { filterable },
+                            naga::ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                            naga::ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                            naga::ScalarKind::Bool => bail!("{} binds a texture of bool", self.file),
+                        },
+                        view_dimension: view_dimension(dim, arrayed),
+                        multisampled: multi,
+                    }
+                }
+                naga::TypeInner::Image { dim, arrayed, class: naga::ImageClass::Depth { multi } } => wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension: view_dimension(dim, arrayed),
+                    multisampled: multi,
+                },
+                _ => bail!("{}: the binding of `{}` is not reflected", self.file, self.name(handle)),
+            },
+        })
+    }
+
+    /// One layout per group in `bind_group_layout_entries`, labelled after the file.
+    pub fn bind_group_layouts(&self, device: &wgpu::Device) -> anyhow::Result<Vec<wgpu::BindGroupLayout>> {
+        Ok(self
+            .bind_group_layout_entries()?
+            .iter()
+            .enumerate()
+            .map(|(group, entries)| {
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some(&format!("{} group {}", self.file, group)),
+                    entries,
+                })
+            })
+            .collect())
+    }
+
+    pub fn pipeline_layout(&self, device: &wgpu::Device, bind_group_layouts: &[wgpu::BindGroupLayout]) -> wgpu::PipelineLayout {
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&self.file),
+            bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
+            push_constant_ranges: &[],
+        })
+    }
+
+    /// Attributes for the vertex stage's inputs at `locations`, packed into one buffer in that
+    /// order, and the buffer's array stride.
+    pub fn vertex_attributes(&self, locations: &[u32]) -> anyhow::Result<(Vec<wgpu::VertexAttribute>, wgpu::BufferAddress)> {
+        let entry_point = self
+            .entry_points
+            .iter()
+            .map(|&index| &self.module.entry_points[index])
+            .find(|entry_point| entry_point.stage == naga::ShaderStage::Vertex)
+            .ok_or_else(|| anyhow!("{}: no vertex stage among the entry points", self.file))?;
+        let mut inputs = BTreeMap::new();
+        for argument in &entry_point.function.arguments {
+            match (&argument.binding, &self.module.types[argument.ty].inner) {
+                (Some(naga::Binding::Location { location, .. }), _) => {
+                    inputs.insert(*location, argument.ty);
+                }
+                (None, naga::TypeInner::Struct { members, .. }) => {
+                    for member in members {
+                        if let Some(naga::Binding::Location { location, .. }) = member.binding {
+                            inputs.insert(location, member.ty);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut attributes = Vec::with_capacity(locations.len());
+        let mut offset = 0;
+        for &location in locations {
+            let ty = inputs
+                .get(&location)
+                .ok_or_else(|| anyhow!("{}: `{}` has no input at location {}", self.file, entry_point.name, location))?;
+            let format = vertex_format(&self.module.types[*ty].inner)
+                .ok_or_else(|| anyhow!("{}: location {} of `{}` has no vertex format", self.file, location, entry_point.name))?;
+            attributes.push(wgpu::VertexAttribute { format, offset, shader_location: location });
+            offset += format.size();
+        }
+        Ok((attributes, offset))
+    }
+
+    /// The workgroup size of the compute stage among the entry points.
+    pub fn workgroup_size(&self) -> anyhow::Result<[u32; 3]> {
+        self.entry_points
+            .iter()
+            .map(|&index| &self.module.entry_points[index])
+            .find(|entry_point| entry_point.stage == naga::ShaderStage::Compute)
+            .map(|entry_point| entry_point.workgroup_size)
+            .ok_or_else(|| anyhow!("{}: no compute stage among the entry points", self.file))
+    }
+
+    /// Checks that a buffer of `size` bytes holds what `[[group(group), binding(binding)]]`
+    /// declares: exactly its size, or for a struct ending in a runtime-sized array, the fixed
+    /// part and a whole number of elements, at least one since wgpu rejects empty bindings.
+    pub fn check_buffer_size(&self, group: u32, binding: u32, size: u64) -> anyhow::Result<()> {
+        let (handle, _) = self
+            .bindings
+            .get(&(group, binding))
+            .ok_or_else(|| anyhow!("{}: the entry points use no binding {} in group {}", self.file, binding, group))?;
+        let (handle, global) = (*handle, &self.module.global_variables[*handle]);
+        let declared = match &self.module.types[global.ty].inner {
+            naga::TypeInner::Struct { members, .. } => members.last().and_then(|last| match self.module.types[last.ty].inner {
+                naga::TypeInner::Array { size: naga::ArraySize::Dynamic, stride, .. } => Some((last.offset as u64, stride as u64)),
+                _ => None,
+            }),
+            _ => None,
+        };
+        match declared {
+            Some((fixed, stride)) if size < fixed + stride || (size - fixed) % stride != 0 => bail!(
+                "{}: `{}` (group {}, binding {}) is {} bytes and then at least one element of {}, but the buffer is {} bytes",
+                self.file, self.name(handle), group, binding, fixed, stride, size,
+            ),
+            None if size != self.layouter[global.ty].size as u64 => bail!(
+                "{}: `{}` (group {}, binding {}) is {} bytes, but the buffer is {} bytes",
+                self.file, self.name(handle), group, binding, self.layouter[global.ty].size, size,
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    fn name(&self, handle: Handle<naga::GlobalVariable>) -> &str {
+        self.module.global_variables[handle].name.as_deref().unwrap_or("?")
+    }
+}
+
+fn view_dimension(dim: naga::ImageDimension, arrayed: bool) -> wgpu::TextureViewDimension {
+    match (dim, arrayed) {
+        (naga::ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+        (naga::ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+        (naga::ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+        (naga::ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+        (naga::ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+        (naga::ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+    }
+}
+
+/// The 32-bit format matching a scalar or vector input.
+fn vertex_format(inner: &naga::TypeInner) -> Option<wgpu::VertexFormat> {
+    use wgpu::VertexFormat as F;
+    let (kind, size) = match *inner {
+        naga::TypeInner::Scalar { kind, width: 4 } => (kind, 1),
+        naga::TypeInner::Vector { size, kind, width: 4 } => (kind, size as u8),
+        _ => return None,
+    };
+    Some(match (kind, size) {
+        (naga::ScalarKind::Float, 1) => F::Float32,
+        (naga::ScalarKind::Float, 2) => F::Float32x2,
+        (naga::ScalarKind::Float, 3) => F::Float32x3,
+        (naga::ScalarKind::Float, 4) => F::Float32x4,
+        (naga::ScalarKind::Uint, 1) => F::Uint32,
+        (naga::ScalarKind::Uint, 2) => F::Uint32x2,
+        (naga::ScalarKind::Uint, 3) => F::Uint32x3,
+        (naga::ScalarKind::Uint, 4) => F::Uint32x4,
+        (naga::ScalarKind::Sint, 1) => F::Sint32,
+        (naga::ScalarKind::Sint, 2) => F::Sint32x2,
+        (naga::ScalarKind::Sint, 3) => F::Sint32x3,
+        (naga::ScalarKind::Sint, 4) => F::Sint32x4,
+        _ => return None,
+    })
+}
@@ -0,0 +1,168 @@
+#![allow(dead_code)]
+use std::{
+    fs::File,
+    io::BufWriter,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+};
+use image::codecs::gif::{GifEncoder, Repeat};
+
+/// An offscreen color target that can be copied back to the CPU. Created in the surface format,
+/// a window's pipelines can draw into it unchanged to capture what is on screen.
+pub struct Capture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub width: u32,
+    pub height: u32,
+    pub format: wgpu::TextureFormat,
+}
+
+impl Capture {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Capture Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self { texture, view, width, height, format }
+    }
+
+    /// Copies the texture out and returns tightly packed RGBA8 rows. sRGB formats already hold
+    /// sRGB-encoded bytes, which is what PNG expects, so only BGRA needs its channels swapped.
+    pub async fn read_rgba(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<Vec<u8>> {
+        let swap_red_blue = match self.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => anyhow::bail!("cannot convert {:?} to RGBA8", format),
+        };
+
+        // rows copied out of a texture have to be padded to COPY_BYTES_PER_ROW_ALIGNMENT
+        let unpadded_bytes_per_row = 4 * self.width;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align) * align;
+
+        let read_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Read Buffer"),
+            size: (padded_bytes_per_row * self.height) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &read_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: NonZeroU32::new(self.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let read_buffer_slice = read_buffer.slice(..);
+        let read_buffer_future = read_buffer_slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        read_buffer_future.await?;
+
+        let data = read_buffer_slice.get_mapped_range();
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * self.height) as usize);
+        for row in data.chunks(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+        drop(data);
+        read_buffer.unmap();
+
+        if swap_red_blue {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        Ok(pixels)
+    }
+
+    pub async fn save_png(&self, device: &wgpu::Device, queue: &wgpu::Queue, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let pixels = self.read_rgba(device, queue).await?;
+        image::save_buffer(path, &pixels, self.width, self.height, image::ColorType::Rgba8)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+    }
+}
+
+/// The first of `PREFIX_001.png`, `PREFIX_002.png`, ... that does not exist yet.
+pub fn next_path(prefix: &str) -> PathBuf {
+    (1..)
+        .map(|i| PathBuf::from(format!("{}_{:03}.png", prefix, i)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+/// A sequence of frames written as `frame_00000.png`, `frame_00001.png`, ... into a directory, or
+/// encoded into a looping animated GIF when the path ends in `.gif`.
+pub enum Recording {
+    Pngs { dir: PathBuf, frames: usize },
+    Gif { encoder: GifEncoder<BufWriter<File>>, delay: image::Delay, frames: usize },
+}
+
+impl Recording {
+    /// `fps` only sets the GIF frame delay; PNG sequences carry no timing.
+    pub fn new(path: impl AsRef<Path>, fps: u32) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let is_gif = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+        if is_gif {
+            let file = File::create(path).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+            let mut encoder = GifEncoder::new(BufWriter::new(file));
+            encoder.set_repeat(Repeat::Infinite)?;
+            let delay = image::Delay::from_numer_denom_ms(1000, fps.max(1));
+            Ok(Self::Gif { encoder, delay, frames: 0 })
+        } else {
+            std::fs::create_dir_all(path).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+            Ok(Self::Pngs { dir: path.to_path_buf(), frames: 0 })
+        }
+    }
+
+    /// Appends tightly packed RGBA8 pixels, as returned by `Capture::read_rgba`.
+    pub fn push(&mut self, pixels: Vec<u8>, width: u32, height: u32) -> anyhow::Result<()> {
+        let image = image::RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| anyhow::anyhow!("frame is not {}x{} RGBA8", width, height))?;
+        match self {
+            Self::Pngs { dir, frames } => {
+                let path = dir.join(format!("frame_{:05}.png", frames));
+                image.save(&path).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+                *frames += 1;
+            }
+            Self::Gif { encoder, delay, frames } => {
+                encoder.encode_frame(image::Frame::from_parts(image, 0, 0, *delay))?;
+                *frames += 1;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn frames(&self) -> usize {
+        match self {
+            Self::Pngs { frames, .. } | Self::Gif { frames, .. } => *frames,
+        }
+    }
+}
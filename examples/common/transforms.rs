@@ -12,6 +12,148 @@ pub const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
     0.0, 0.0, 0.5, 1.0,
 );
 
+/// Which adapter to pick out of `Instance::enumerate_adapters`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AdapterSelector {
+    /// Case-insensitive substring of `AdapterInfo::name`.
+    Name(String),
+    /// Position in the list returned by `list_adapters`.
+    Index(usize),
+}
+
+#[derive(Clone, Debug)]
+pub struct WgpuConfig {
+    pub backends: wgpu::Backends,
+    pub power_preference: wgpu::PowerPreference,
+    pub force_fallback_adapter: bool,
+    pub features: wgpu::Features,
+    pub limits: wgpu::Limits,
+    pub adapter: Option<AdapterSelector>,
+}
+
+impl Default for WgpuConfig {
+    fn default() -> Self {
+        Self {
+            backends: wgpu::Backends::all(),
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter: false,
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits::default(),
+            adapter: None,
+        }
+    }
+}
+
+impl WgpuConfig {
+    /// Default config overridden by `WGPU_BACKEND` (e.g. "vulkan,gl"), `WGPU_POWER_PREF`
+    /// ("low"/"high"), `WGPU_FALLBACK_ADAPTER`, `WGPU_ADAPTER_NAME` and `WGPU_ADAPTER_INDEX`.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Some(backends) = wgpu::util::backend_bits_from_env() {
+            config.backends = backends;
+        }
+        if let Some(power_preference) = wgpu::util::power_preference_from_env() {
+            config.power_preference = power_preference;
+        }
+        if let Ok(fallback) = std::env::var("WGPU_FALLBACK_ADAPTER") {
+            config.force_fallback_adapter = matches!(fallback.as_str(), "1" | "true");
+        }
+        if let Ok(name) = std::env::var("WGPU_ADAPTER_NAME") {
+            config.adapter = Some(AdapterSelector::Name(name));
+        }
+        if let Some(index) = std::env::var("WGPU_ADAPTER_INDEX").ok().and_then(|s| s.parse().ok()) {
+            config.adapter = Some(AdapterSelector::Index(index));
+        }
+        config
+    }
+
+    pub async fn request_adapter(
+        &self,
+        instance: &wgpu::Instance,
+        compatible_surface: Option<&wgpu::Surface>,
+    ) -> anyhow::Result<wgpu::Adapter> {
+        let adapter = match &self.adapter {
+            Some(selector) => {
+                let mut adapters = instance.enumerate_adapters(self.backends).enumerate();
+                let found = match selector {
+                    AdapterSelector::Name(name) => {
+                        let name = name.to_lowercase();
+                        adapters.find(|(_, a)| a.get_info().name.to_lowercase().contains(&name))
+                    }
+                    AdapterSelector::Index(index) => adapters.find(|(i, _)| i == index),
+                };
+                match found {
+                    Some((_, adapter)) => adapter,
+                    None => anyhow::bail!(
+                        "no adapter matches {:?} on {:?}, available: {}",
+                        selector, self.backends, describe_adapters(instance, self.backends),
+                    ),
+                }
+            }
+            None => instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: self.power_preference,
+                    compatible_surface,
+                    force_fallback_adapter: self.force_fallback_adapter,
+                })
+                .await
+                .ok_or_else(|| anyhow::anyhow!(
+                    "no suitable adapter on {:?} (fallback: {}), available: {}",
+                    self.backends, self.force_fallback_adapter, describe_adapters(instance, self.backends),
+                ))?,
+        };
+
+        let info = adapter.get_info();
+        if let Some(surface) = compatible_surface {
+            if !adapter.is_surface_supported(surface) {
+                anyhow::bail!("adapter {:?} ({:?}) cannot present to this surface", info.name, info.backend);
+            }
+        }
+        let missing = self.features - adapter.features();
+        if !missing.is_empty() {
+            anyhow::bail!("adapter {:?} ({:?}) is missing features {:?}", info.name, info.backend, missing);
+        }
+        log::info!("using adapter {:?} ({:?}, {:?})", info.name, info.backend, info.device_type);
+        Ok(adapter)
+    }
+
+    pub async fn request_device(&self, adapter: &wgpu::Adapter) -> anyhow::Result<(wgpu::Device, wgpu::Queue)> {
+        adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    features: self.features,
+                    limits: self.limits.clone(),
+                },
+                None,
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("{} on {:?} with limits {:?}", e, adapter.get_info().name, self.limits))
+    }
+}
+
+/// Info for every adapter on `backends`, in the order `AdapterSelector::Index` refers to.
+pub fn list_adapters(backends: wgpu::Backends) -> Vec<wgpu::AdapterInfo> {
+    let instance = wgpu::Instance::new(backends);
+    instance.enumerate_adapters(backends).map(|a| a.get_info()).collect()
+}
+
+fn describe_adapters(instance: &wgpu::Instance, backends: wgpu::Backends) -> String {
+    let names = instance
+        .enumerate_adapters(backends)
+        .enumerate()
+        .map(|(i, a)| {
+            let info = a.get_info();
+            format!("[{}] {} ({:?}, {:?})", i, info.name, info.backend, info.device_type)
+        })
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join(", ")
+    }
+}
+
 pub struct InitWgpu {
     pub surface: wgpu::Surface,
     pub device: wgpu::Device,
@@ -21,47 +163,35 @@ pub struct InitWgpu {
 }
 
 impl InitWgpu {
-    pub async fn init_wgpu(window: &Window) -> Self {
+    pub async fn init_wgpu(window: &Window) -> anyhow::Result<Self> {
+        Self::init_wgpu_with(window, &WgpuConfig::from_env()).await
+    }
+
+    pub async fn init_wgpu_with(window: &Window, wgpu_config: &WgpuConfig) -> anyhow::Result<Self> {
         let size = window.inner_size();
-        let instance = wgpu::Instance::new(wgpu::Backends::all());
+        let instance = wgpu::Instance::new(wgpu_config.backends);
         let surface = unsafe { instance.create_surface(window) };
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    features: wgpu::Features::empty(),
-                    limits: wgpu::Limits::default(),
-                },
-                None,
-            )
-            .await
-            .unwrap();
+        let adapter = wgpu_config.request_adapter(&instance, Some(&surface)).await?;
+        let (device, queue) = wgpu_config.request_device(&adapter).await?;
 
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_preferred_format(&adapter).unwrap(),
+            format: surface
+                .get_preferred_format(&adapter)
+                .ok_or_else(|| anyhow::anyhow!("surface is incompatible with the adapter"))?,
             width: size.width,
             height: size.height,
             present_mode: wgpu::PresentMode::Fifo,
         };
         surface.configure(&device, &config);
 
-        Self{
+        Ok(Self{
             surface,
             device,
             queue,
             config,
             size,
-        }
+        })
     }
 }
 
@@ -72,21 +202,222 @@ pub fn create_transforms(translation: [f32; 3], rotation: [f32; 3], scaling: [f3
     let rotate_mat_z = Matrix4::from_angle_z(Rad(rotation[2]));
     let scale_mat = Matrix4::from_nonuniform_scale(scaling[0], scaling[1], scaling[2]);
 
-    let model_mat = trans_mat * rotate_mat_x * rotate_mat_y * rotate_mat_z * scale_mat;
+    trans_mat * rotate_mat_x * rotate_mat_y * rotate_mat_z * scale_mat
+}
+
+/// Scale, then rotation, then translation, as one value that can be composed, inverted and
+/// interpolated without the gimbal lock of `create_transforms`' Euler angles.
+///
+/// Composition and inversion are exact for uniform scales; a non-uniform scale followed by a
+/// rotation is a shear, which this representation cannot hold.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::new(Vector3::zero(), Quaternion::one(), Vector3::new(1.0, 1.0, 1.0))
+    }
+}
+
+impl Transform {
+    pub fn new(translation: Vector3<f32>, rotation: Quaternion<f32>, scale: Vector3<f32>) -> Self {
+        Self { translation, rotation, scale }
+    }
+
+    /// The same transform as `create_transforms` with the same arguments.
+    pub fn from_euler(translation: [f32; 3], rotation: [f32; 3], scaling: [f32; 3]) -> Self {
+        let rotation = Quaternion::from_angle_x(Rad(rotation[0]))
+            * Quaternion::from_angle_y(Rad(rotation[1]))
+            * Quaternion::from_angle_z(Rad(rotation[2]));
+        Self::new(translation.into(), rotation, scaling.into())
+    }
+
+    /// Splits an affine matrix without shear or projection into its parts. A mirroring matrix
+    /// comes back with a negative x scale; `None` if an axis is scaled to zero.
+    pub fn from_matrix(m: Matrix4<f32>) -> Option<Self> {
+        let translation = m.w.truncate();
+        let (x, y, z) = (m.x.truncate(), m.y.truncate(), m.z.truncate());
+        let mut scale = Vector3::new(x.magnitude(), y.magnitude(), z.magnitude());
+        if scale.x.abs() < f32::EPSILON || scale.y.abs() < f32::EPSILON || scale.z.abs() < f32::EPSILON {
+            return None;
+        }
+        if x.cross(y).dot(z) < 0.0 {
+            scale.x = -scale.x;
+        }
+        let rotation = Quaternion::from(Matrix3::from_cols(x / scale.x, y / scale.y, z / scale.z)).normalize();
+        Some(Self::new(translation, rotation, scale))
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
 
-    model_mat
+    pub fn transform_point(&self, point: Point3<f32>) -> Point3<f32> {
+        Point3::from_vec(self.rotation.rotate_vector(point.to_vec().mul_element_wise(self.scale)) + self.translation)
+    }
+
+    pub fn transform_vector(&self, vector: Vector3<f32>) -> Vector3<f32> {
+        self.rotation.rotate_vector(vector.mul_element_wise(self.scale))
+    }
+
+    /// `self` applied after `other`, like `self.matrix() * other.matrix()`.
+    pub fn compose(&self, other: &Transform) -> Self {
+        Self::new(
+            self.transform_point(Point3::from_vec(other.translation)).to_vec(),
+            (self.rotation * other.rotation).normalize(),
+            self.scale.mul_element_wise(other.scale),
+        )
+    }
+
+    pub fn inverse(&self) -> Self {
+        let rotation = self.rotation.conjugate();
+        let scale = Vector3::new(1.0 / self.scale.x, 1.0 / self.scale.y, 1.0 / self.scale.z);
+        let translation = -rotation.rotate_vector(self.translation).mul_element_wise(scale);
+        Self::new(translation, rotation, scale)
+    }
+
+    /// Interpolates translation and scale linearly and rotation along the shorter arc,
+    /// normalized but not at constant angular speed; cheaper than `slerp` for nearby rotations.
+    pub fn lerp(&self, other: &Transform, amount: f32) -> Self {
+        Self::new(
+            self.translation.lerp(other.translation, amount),
+            self.rotation.nlerp(other.rotation, amount),
+            self.scale.lerp(other.scale, amount),
+        )
+    }
+
+    /// Like `lerp`, but the rotation turns at constant angular speed.
+    pub fn slerp(&self, other: &Transform, amount: f32) -> Self {
+        Self::new(
+            self.translation.lerp(other.translation, amount),
+            self.rotation.slerp(other.rotation, amount),
+            self.scale.lerp(other.scale, amount),
+        )
+    }
+}
+
+impl std::ops::Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, other: Transform) -> Transform {
+        self.compose(&other)
+    }
+}
+
+impl From<Transform> for Matrix4<f32> {
+    fn from(transform: Transform) -> Matrix4<f32> {
+        transform.matrix()
+    }
 }
 
 pub fn create_view(camera_position: Point3<f32>, look_direction: Point3<f32>, up_directrion: Vector3<f32>) -> Matrix4<f32> {
     Matrix4::look_at_rh(camera_position, look_direction, up_directrion)
 }
 
+/// Flips wgpu depth so the near plane lands on 1 and the far plane on 0, which spreads float
+/// precision far more evenly over the depth range.
+#[rustfmt::skip]
+pub const REVERSE_Z_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, -1.0, 0.0,
+    0.0, 0.0, 1.0, 1.0,
+);
+
+/// Maps right-handed view space, looking down -z, to wgpu clip space with depth 0 at `near` and
+/// 1 at `far`, or the other way around with `reverse_z`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// `far` may be `f32::INFINITY`.
+    Perspective { fovy: Rad<f32>, near: f32, far: f32, reverse_z: bool },
+    /// `height` view space units from bottom to top; the width follows the aspect ratio.
+    Orthographic { height: f32, near: f32, far: f32, reverse_z: bool },
+}
+
+impl Default for Projection {
+    /// 72° vertical field of view from 0.1 to 100.
+    fn default() -> Self {
+        Self::perspective(Rad(2.0 * PI / 5.0), 0.1, 100.0)
+    }
+}
+
+impl Projection {
+    pub fn perspective(fovy: impl Into<Rad<f32>>, near: f32, far: f32) -> Self {
+        Self::Perspective { fovy: fovy.into(), near, far, reverse_z: false }
+    }
+
+    /// A perspective without a far plane, so nothing in front of the camera is clipped by depth.
+    pub fn infinite_perspective(fovy: impl Into<Rad<f32>>, near: f32) -> Self {
+        Self::perspective(fovy, near, f32::INFINITY)
+    }
+
+    pub fn orthographic(height: f32, near: f32, far: f32) -> Self {
+        Self::Orthographic { height, near, far, reverse_z: false }
+    }
+
+    pub fn with_reverse_z(self) -> Self {
+        match self {
+            Self::Perspective { fovy, near, far, .. } => Self::Perspective { fovy, near, far, reverse_z: true },
+            Self::Orthographic { height, near, far, .. } => Self::Orthographic { height, near, far, reverse_z: true },
+        }
+    }
+
+    pub fn reverse_z(&self) -> bool {
+        match *self {
+            Self::Perspective { reverse_z, .. } | Self::Orthographic { reverse_z, .. } => reverse_z,
+        }
+    }
+
+    /// The depth test that keeps the closer fragment.
+    pub fn depth_compare(&self) -> wgpu::CompareFunction {
+        if self.reverse_z() { wgpu::CompareFunction::Greater } else { wgpu::CompareFunction::Less }
+    }
+
+    /// The depth buffer value of the far end, to clear to.
+    pub fn far_depth(&self) -> f32 {
+        if self.reverse_z() { 0.0 } else { 1.0 }
+    }
+
+    pub fn matrix(&self, aspect: f32) -> Matrix4<f32> {
+        let (projection, reverse_z) = match *self {
+            Self::Perspective { fovy, near, far, reverse_z } if far.is_infinite() => {
+                // the limit of cgmath's perspective as far goes to infinity
+                let f = 1.0 / (fovy.0 / 2.0).tan();
+                #[rustfmt::skip]
+                let projection = Matrix4::new(
+                    f / aspect, 0.0, 0.0, 0.0,
+                    0.0, f, 0.0, 0.0,
+                    0.0, 0.0, -1.0, -1.0,
+                    0.0, 0.0, -2.0 * near, 0.0,
+                );
+                (projection, reverse_z)
+            }
+            Self::Perspective { fovy, near, far, reverse_z } => (perspective(fovy, aspect, near, far), reverse_z),
+            Self::Orthographic { height, near, far, reverse_z } => {
+                let (half_width, half_height) = (0.5 * height * aspect, 0.5 * height);
+                (ortho(-half_width, half_width, -half_height, half_height, near, far), reverse_z)
+            }
+        };
+        if reverse_z {
+            REVERSE_Z_MATRIX * OPENGL_TO_WGPU_MATRIX * projection
+        } else {
+            OPENGL_TO_WGPU_MATRIX * projection
+        }
+    }
+}
+
+/// The default `Projection`, or the fixed orthographic box from (-4, -3, -1) to (4, 3, 6)
+/// whatever the aspect; `Projection::orthographic` follows the aspect instead.
 pub fn create_projection(aspect:f32, is_perspective:bool) -> Matrix4<f32> {
-    let project_mat:Matrix4<f32>;
     if is_perspective {
-        project_mat = OPENGL_TO_WGPU_MATRIX * perspective(Rad(2.0*PI/5.0), aspect, 0.1, 100.0);
+        Projection::default().matrix(aspect)
     } else {
-        project_mat = OPENGL_TO_WGPU_MATRIX * ortho(-4.0, 4.0, -3.0, 3.0, -1.0, 6.0);
+        OPENGL_TO_WGPU_MATRIX * ortho(-4.0, 4.0, -3.0, 3.0, -1.0, 6.0)
     }
-    project_mat
 }
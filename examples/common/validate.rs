@@ -0,0 +1,200 @@
+#![allow(dead_code)]
+use std::{fmt, ops::Range};
+use naga::valid::{Capabilities, EntryPointError, FunctionError, ModuleInfo, ValidationError, ValidationFlags, Validator};
+use preprocess::Preprocessor;
+
+#[path = "preprocess.rs"]
+pub mod preprocess;
+
+/// Every WGSL file the examples load, named relative to the crate root, for `--check-shaders`.
+pub const SHADERS: [(&str, &str); 8] = [
+    ("examples/01/shader.wgsl", include_str!("../01/shader.wgsl")),
+    ("examples/common/math.wgsl", include_str!("math.wgsl")),
+    ("examples/13/boids.wgsl", include_str!("../13/boids.wgsl")),
+    ("examples/13/boids_shared.wgsl", include_str!("../13/boids_shared.wgsl")),
+    ("examples/13/boids3d.wgsl", include_str!("../13/boids3d.wgsl")),
+    ("examples/13/boids_grid.wgsl", include_str!("../13/boids_grid.wgsl")),
+    ("examples/13/rotate2d.wgsl", include_str!("../13/rotate2d.wgsl")),
+    ("examples/13/transform3d.wgsl", include_str!("../13/transform3d.wgsl")),
+];
+
+/// A WGSL parse or validation error, located in its source file.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub file: String,
+    /// 1-based, like the `line:column` editors and compilers print.
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    /// The offending line followed by a line of carets under the error.
+    pub snippet: String,
+}
+
+impl Diagnostic {
+    /// An error at `line` and `column`, with `width` carets under `text`, the line's contents.
+    fn at(file: &str, line: usize, column: usize, width: usize, text: &str, message: String) -> Self {
+        let number = line.to_string();
+        let gutter = " ".repeat(number.len());
+        let snippet = format!(
+            "{} |\n{} | {}\n{} | {}{}",
+            gutter, number, text.trim_end(), gutter, " ".repeat(column - 1), "^".repeat(width.max(1)),
+        );
+        Self { file: file.to_string(), line, column, message, snippet }
+    }
+}
+
+impl From<preprocess::Error> for Diagnostic {
+    fn from(e: preprocess::Error) -> Self {
+        let width = e.text.trim().chars().count();
+        Self::at(&e.file, e.line, e.column, width, &e.text, e.message)
+    }
+}
+
+/// The line, column, width and line contents of `span` in `source`.
+fn locate(source: &str, span: Range<usize>) -> (usize, usize, usize, &str) {
+    // naga's expression spans can start at the whitespace before the expression
+    let leading = source.get(span.clone()).map_or(0, |text| text.len() - text.trim_start().len());
+    let start = (span.start + leading).min(source.len());
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..].find('\n').map_or(source.len(), |i| start + i);
+    let line = source[..start].matches('\n').count() + 1;
+    let column = source[line_start..start].chars().count() + 1;
+    let width = source[start..span.end.clamp(start, line_end)].chars().count();
+    (line, column, width, &source[line_start..line_end])
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error: {}\n --> {}:{}:{}\n{}", self.message, self.file, self.line, self.column, self.snippet)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// Preprocesses, parses and validates `source` with naga, the checks wgpu runs when the module is
+/// created. `file` only names the source in a diagnostic, which points into the file a line came
+/// from, `source` or an included snippet.
+pub fn validate(file: &str, source: &str) -> Result<(naga::Module, ModuleInfo), Diagnostic> {
+    validate_with(&Preprocessor::new(), file, source)
+}
+
+/// `validate` after `preprocessor` has resolved the `#include`s and `#define`s.
+pub fn validate_with(preprocessor: &Preprocessor, file: &str, source: &str) -> Result<(naga::Module, ModuleInfo), Diagnostic> {
+    compile(preprocessor, file, source).map(|(_, module, info)| (module, info))
+}
+
+fn compile(preprocessor: &Preprocessor, file: &str, source: &str) -> Result<(String, naga::Module, ModuleInfo), Diagnostic> {
+    let preprocessed = preprocessor.run(file, source)?;
+    let source = preprocessed.source.as_str();
+    let diagnostic = |span: Range<usize>, message: String| {
+        let (line, column, width, text) = locate(source, span);
+        let (file, line) = preprocessed.origin(line);
+        Diagnostic::at(file, line, column, width, text, message)
+    };
+    let module = naga::front::wgsl::parse_str(source).map_err(|e| {
+        let (line, column) = e.location(source);
+        let start = source.split('\n').take(line - 1).map(|line| line.len() + 1).sum::<usize>() + column - 1;
+        diagnostic(start..start, e.to_string())
+    })?;
+    let info = Validator::new(ValidationFlags::all(), Capabilities::empty())
+        .validate(&module)
+        .map_err(|e| {
+            let span = error_span(&module, &e).unwrap_or_else(|| declaration_span(source, &e));
+            diagnostic(span, error_chain(&e))
+        })?;
+    Ok((preprocessed.source.clone(), module, info))
+}
+
+/// Creates a shader module from WGSL that has passed `validate`, panicking with the diagnostic
+/// otherwise instead of leaving wgpu to report it. The file name is also the module's label.
+pub fn shader_module(device: &wgpu::Device, file: &str, source: &str) -> wgpu::ShaderModule {
+    shader_module_with(device, &Preprocessor::new(), file, source)
+}
+
+/// `shader_module` with the `#define`s of `preprocessor`.
+pub fn shader_module_with(device: &wgpu::Device, preprocessor: &Preprocessor, file: &str, source: &str) -> wgpu::ShaderModule {
+    try_shader_module_with(device, preprocessor, file, source).unwrap_or_else(|diagnostic| panic!("{}", diagnostic))
+}
+
+/// `shader_module_with`, returning the diagnostic instead of panicking.
+pub fn try_shader_module_with(
+    device: &wgpu::Device,
+    preprocessor: &Preprocessor,
+    file: &str,
+    source: &str,
+) -> Result<wgpu::ShaderModule, Diagnostic> {
+    let (source, _, _) = compile(preprocessor, file, source)?;
+    Ok(device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some(file),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    }))
+}
+
+/// Validates every file in `SHADERS`, printing each diagnostic. False if any failed.
+pub fn check_shaders() -> bool {
+    let mut ok = true;
+    for (file, source) in SHADERS {
+        match validate(file, source) {
+            Ok(_) => println!("ok      {}", file),
+            Err(diagnostic) => {
+                println!("FAILED  {}\n{}\n", file, diagnostic);
+                ok = false;
+            }
+        }
+    }
+    ok
+}
+
+/// The error and its causes, "Function [0] 'main' is invalid: Expression [3] is invalid: ...".
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message += &format!(": {}", cause);
+        source = cause.source();
+    }
+    message
+}
+
+/// The expression or variable naga recorded a span for, when the error names one.
+fn error_span(module: &naga::Module, error: &ValidationError) -> Option<Range<usize>> {
+    match error {
+        ValidationError::GlobalVariable { handle, .. } => module.global_variables.get_span(*handle).to_range(),
+        ValidationError::Function { handle, error, .. } => function_span(&module.functions[*handle], error),
+        ValidationError::EntryPoint { stage, name, error: EntryPointError::Function(error) } => module
+            .entry_points
+            .iter()
+            .find(|entry_point| entry_point.stage == *stage && entry_point.name == *name)
+            .and_then(|entry_point| function_span(&entry_point.function, error)),
+        _ => None,
+    }
+}
+
+fn function_span(function: &naga::Function, error: &FunctionError) -> Option<Range<usize>> {
+    let expression = match *error {
+        FunctionError::Expression { handle, .. }
+        | FunctionError::InvalidReturnType(Some(handle))
+        | FunctionError::InvalidIfType(handle)
+        | FunctionError::InvalidSwitchType(handle)
+        | FunctionError::InvalidStorePointer(handle)
+        | FunctionError::InvalidStoreValue(handle)
+        | FunctionError::InvalidStoreTypes { value: handle, .. } => handle,
+        FunctionError::LocalVariable { handle, .. } => return function.local_variables.get_span(handle).to_range(),
+        _ => return None,
+    };
+    function.expressions.get_span(expression).to_range()
+}
+
+/// Without a recorded span, the declaration of whatever the error names, or else the start.
+fn declaration_span(source: &str, error: &ValidationError) -> Range<usize> {
+    let declaration = match error {
+        ValidationError::Type { name, .. } => format!("struct {}", name),
+        ValidationError::Constant { name, .. } | ValidationError::GlobalVariable { name, .. } => name.clone(),
+        ValidationError::Function { name, .. } | ValidationError::EntryPoint { name, .. } => format!("fn {}", name),
+        _ => String::new(),
+    };
+    match source.find(declaration.as_str()) {
+        Some(start) if !declaration.is_empty() => start..start + declaration.len(),
+        _ => 0..0,
+    }
+}
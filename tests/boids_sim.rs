@@ -0,0 +1,144 @@
+//! CPU-side checks of the boids simulation's parameters, limits and snapshots, which need no GPU.
+#![allow(dead_code)]
+#[path="../examples/13/boids_sim.rs"]
+mod boids_sim;
+#[path="../examples/common/compute.rs"]
+mod compute;
+#[path="../examples/common/reflect.rs"]
+mod reflect;
+#[path="../examples/common/validate.rs"]
+mod validate;
+
+use std::path::PathBuf;
+use boids_sim::{SimParams, Snapshot};
+
+fn scratch(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+#[test]
+fn boundary_margin_must_be_positive() {
+    let mut params = SimParams::default();
+    assert!(params.set_flag("--boundary-margin", "0.05").unwrap());
+    assert_eq!(params.boundary_margin, 0.05);
+    for value in ["0", "-0.1", "NaN"] {
+        let error = SimParams::default().set_flag("--boundary-margin", value).unwrap_err();
+        assert!(error.to_string().contains("boundary_margin must be positive"), "{}", error);
+    }
+
+    let path = scratch("zero_margin.json");
+    std::fs::write(&path, r#"{ "boundary": "steer", "boundary_margin": 0.0 }"#).unwrap();
+    let error = SimParams::load(&path).unwrap_err();
+    assert!(error.to_string().contains("boundary_margin must be positive"), "{}", error);
+}
+
+#[test]
+fn max_particles_respects_dispatch_and_binding_limits() {
+    let limits = wgpu::Limits::default();
+    let max = boids_sim::Simulation::max_particles(&limits, 64);
+    assert_eq!(max, 65535 * 64);
+    let max = boids_sim::Simulation::max_particles(&limits, 256);
+    assert_eq!(max as u64, limits.max_storage_buffer_binding_size as u64 / boids_sim::PARTICLE_SIZE);
+}
+
+fn snapshot() -> Snapshot {
+    let particles = boids_sim::initial_particles(5, 7);
+    let mut species = boids_sim::SpeciesMatrix::default();
+    species.count = 2;
+    species.weights[1] = [0.0, 1.0, 0.0, 1.0];
+    species.weights[boids_sim::SpeciesMatrix::MAX] = [1.0, 1.0, 1.0, 0.0];
+    species.weights[boids_sim::SpeciesMatrix::MAX + 1] = [1.0, 1.0, 1.0, 0.0];
+    Snapshot {
+        version: Snapshot::VERSION,
+        seed: 7,
+        frame: 123,
+        params: SimParams { boundary: boids_sim::BOUNDARY_STEER, ..SimParams::default() },
+        species,
+        species_ids: vec![0, 1, 0, 1, 0],
+        particles,
+    }
+}
+
+fn assert_same(loaded: &Snapshot, saved: &Snapshot) {
+    assert_eq!(
+        (loaded.version, loaded.seed, loaded.frame, &loaded.species_ids, &loaded.particles),
+        (saved.version, saved.seed, saved.frame, &saved.species_ids, &saved.particles),
+    );
+    assert_eq!(loaded.params, saved.params);
+    assert_eq!(loaded.species, saved.species);
+}
+
+#[test]
+fn snapshot_round_trips() {
+    let saved = snapshot();
+    for name in ["round_trip.boids", "round_trip.json"] {
+        let path = scratch(name);
+        saved.save(&path).unwrap();
+        assert_same(&Snapshot::load(&path).unwrap(), &saved);
+    }
+}
+
+#[test]
+fn snapshot_rejects_bad_magic_and_version() {
+    let path = scratch("bad_magic.boids");
+    snapshot().save(&path).unwrap();
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes[0] = b'X';
+    std::fs::write(&path, &bytes).unwrap();
+    let error = Snapshot::load(&path).unwrap_err();
+    assert!(error.to_string().contains("not a boids snapshot"), "{}", error);
+
+    let path = scratch("bad_version.boids");
+    Snapshot { version: Snapshot::VERSION + 1, ..snapshot() }.save(&path).unwrap();
+    let error = Snapshot::load(&path).unwrap_err();
+    assert!(error.to_string().contains("is not supported"), "{}", error);
+
+    let path = scratch("bad_version.json");
+    Snapshot { version: Snapshot::VERSION + 1, ..snapshot() }.save(&path).unwrap();
+    let error = Snapshot::load(&path).unwrap_err();
+    assert!(error.to_string().contains("is not supported"), "{}", error);
+
+    let path = scratch("truncated.boids");
+    snapshot().save(&path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+    let error = Snapshot::load(&path).unwrap_err();
+    assert!(error.to_string().contains("truncated"), "{}", error);
+}
+
+#[test]
+fn snapshot_rejects_invalid_contents() {
+    let path = scratch("bad_species_id.boids");
+    Snapshot { species_ids: vec![0, 1, 2, 1, 0], ..snapshot() }.save(&path).unwrap();
+    let error = Snapshot::load(&path).unwrap_err();
+    assert!(error.to_string().contains("species id 2 is out of range for 2 species"), "{}", error);
+
+    let path = scratch("bad_species_count.json");
+    let mut species = boids_sim::SpeciesMatrix::default();
+    species.count = boids_sim::SpeciesMatrix::MAX as u32 + 1;
+    Snapshot { species, ..snapshot() }.save(&path).unwrap();
+    let error = Snapshot::load(&path).unwrap_err();
+    assert!(error.to_string().contains("expected 1 to 4 species"), "{}", error);
+
+    for name in ["bad_margin.boids", "bad_margin.json"] {
+        let path = scratch(name);
+        let params = SimParams { boundary_margin: 0.0, ..SimParams::default() };
+        Snapshot { params, ..snapshot() }.save(&path).unwrap();
+        let error = Snapshot::load(&path).unwrap_err();
+        assert!(error.to_string().contains("boundary_margin must be positive"), "{}", error);
+    }
+
+    let path = scratch("bad_boundary.boids");
+    let params = SimParams { boundary: boids_sim::BOUNDARY_NAMES.len() as u32, ..SimParams::default() };
+    Snapshot { params, ..snapshot() }.save(&path).unwrap();
+    let error = Snapshot::load(&path).unwrap_err();
+    assert!(error.to_string().contains("unknown boundary mode"), "{}", error);
+
+    let path = scratch("trailing.boids");
+    snapshot().save(&path).unwrap();
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes.push(0);
+    std::fs::write(&path, &bytes).unwrap();
+    let error = Snapshot::load(&path).unwrap_err();
+    assert!(error.to_string().contains("1 bytes after the species ids"), "{}", error);
+}
@@ -0,0 +1,74 @@
+#![allow(dead_code)]
+#[path="../examples/common/camera.rs"]
+mod camera;
+#[path="../examples/common/transforms.rs"]
+mod transforms;
+
+use camera::{CameraController, FlyController, OrbitController, PanZoomController};
+use cgmath::{prelude::*, Point3, Vector4};
+use winit::{dpi::{PhysicalPosition, PhysicalSize}, event::*};
+
+fn device_id() -> DeviceId {
+    unsafe { DeviceId::dummy() }
+}
+
+#[allow(deprecated)]
+fn cursor_moved(x: f64, y: f64) -> WindowEvent<'static> {
+    WindowEvent::CursorMoved {
+        device_id: device_id(),
+        position: PhysicalPosition::new(x, y),
+        modifiers: ModifiersState::empty(),
+    }
+}
+
+#[allow(deprecated)]
+fn wheel(lines: f32) -> WindowEvent<'static> {
+    WindowEvent::MouseWheel {
+        device_id: device_id(),
+        delta: MouseScrollDelta::LineDelta(0.0, lines),
+        phase: TouchPhase::Moved,
+        modifiers: ModifiersState::empty(),
+    }
+}
+
+#[test]
+fn smoothing_does_not_depend_on_frame_rate() {
+    let remaining = |frames: usize| {
+        let dt = 1.0 / frames as f32;
+        (0..frames).fold(1.0f32, |left, _| left * (1.0 - camera::smoothing(10.0, dt)))
+    };
+    assert!((remaining(30) - remaining(144)).abs() < 1e-5);
+    assert!((remaining(60) - (-10.0f32).exp()).abs() < 1e-5);
+    assert_eq!(camera::smoothing(f32::INFINITY, 0.0), 1.0);
+}
+
+#[test]
+fn orbit_zooms_towards_the_goal() {
+    let mut orbit = OrbitController::new(Point3::new(0.0, 0.0, 0.0), 4.0, 0.8, 0.4);
+    assert!(orbit.window_event(&wheel(1.0)));
+    assert!((orbit.goal.distance - 3.6).abs() < 1e-5);
+    orbit.update(1.0 / 60.0);
+    assert!(orbit.orbit.distance < 4.0 && orbit.orbit.distance > 3.6);
+    for _ in 0..600 {
+        orbit.update(1.0 / 60.0);
+    }
+    assert!(((orbit.eye() - Point3::new(0.0, 0.0, 0.0)).magnitude() - 3.6).abs() < 1e-4);
+}
+
+#[test]
+fn fly_starts_looking_at_its_target() {
+    let fly = FlyController::new(Point3::new(1.0, 2.0, 3.0), Point3::new(-2.0, 0.0, -1.0));
+    let target = fly.view() * Vector4::new(-2.0, 0.0, -1.0, 1.0);
+    assert!(target.x.abs() < 1e-5 && target.y.abs() < 1e-5 && target.z < 0.0, "{:?}", target);
+}
+
+#[test]
+fn pan_zoom_keeps_the_point_under_the_cursor() {
+    let mut view = PanZoomController::new(PhysicalSize::new(800, 600));
+    view.window_event(&cursor_moved(600.0, 150.0));
+    let before = view.world_position([600.0, 150.0]);
+    view.window_event(&wheel(3.0));
+    assert!(view.goal_zoom > 1.0);
+    let after = view.world_position([600.0, 150.0]);
+    assert!((before - after).magnitude() < 1e-5, "{:?} moved to {:?}", before, after);
+}
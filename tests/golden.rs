@@ -0,0 +1,155 @@
+//! Renders the 01 triangle and a fixed-seed boids frame offscreen and compares them with the
+//! reference images in tests/golden.
+//!
+//! The tests need an adapter, so they are ignored by default; run them with
+//! `cargo test --test golden -- --ignored`. They ask for the fallback (software) adapter, such
+//! as lavapipe or llvmpipe, unless `WGPU_FALLBACK_ADAPTER` says otherwise, and fail when there is
+//! none. A mismatch writes the rendered image and a diff next to the test binary's scratch
+//! directory; `UPDATE_GOLDEN=1` rewrites the references instead of comparing.
+//!
+//! The references must come from a software adapter; until they have been rendered on one
+//! there are none, and the tests fail asking for `UPDATE_GOLDEN=1`.
+#![allow(dead_code)]
+#[path="../examples/01/common.rs"]
+mod common;
+#[path="../examples/13/boids_render.rs"]
+mod boids_render;
+#[path="../examples/13/boids_sim.rs"]
+mod boids_sim;
+#[path="../examples/common/compute.rs"]
+mod compute;
+#[path="../examples/common/hot_reload.rs"]
+mod hot_reload;
+#[path="../examples/common/reflect.rs"]
+mod reflect;
+#[path="../examples/common/screenshot.rs"]
+mod screenshot;
+#[path="../examples/common/transforms.rs"]
+mod transforms;
+#[path="../examples/common/validate.rs"]
+mod validate;
+
+use std::{borrow::Cow, path::PathBuf};
+
+const SIZE: u32 = 256;
+/// Largest per-channel difference a pixel may have from the reference.
+const TOLERANCE: u8 = 8;
+/// Pixels allowed beyond `TOLERANCE`, for rasterizers that decide coverage on triangle edges
+/// differently.
+const MAX_MISMATCHED: usize = 64;
+
+const BOIDS_PARTICLES: u32 = 512;
+const BOIDS_SEED: u64 = 42;
+const BOIDS_STEPS: usize = 10;
+
+fn wgpu_config() -> transforms::WgpuConfig {
+    let mut config = transforms::WgpuConfig {
+        limits: wgpu::Limits::downlevel_defaults(),
+        ..transforms::WgpuConfig::from_env()
+    };
+    if std::env::var_os("WGPU_FALLBACK_ADAPTER").is_none() {
+        config.force_fallback_adapter = true;
+    }
+    config
+}
+
+/// A device from `wgpu_config`, panicking when this machine has no such adapter.
+fn device(config: &transforms::WgpuConfig) -> (wgpu::Device, wgpu::Queue) {
+    let instance = wgpu::Instance::new(config.backends);
+    let adapter = pollster::block_on(config.request_adapter(&instance, None))
+        .unwrap_or_else(|e| panic!("golden image tests need an adapter: {}", e));
+    pollster::block_on(config.request_device(&adapter)).unwrap()
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.png", name))
+}
+
+/// Compares RGBA8 `pixels` with tests/golden/NAME.png. On failure the rendered image and a diff,
+/// red where a pixel is beyond `TOLERANCE` and a faded copy of the reference elsewhere, are
+/// written to NAME_actual.png and NAME_diff.png in the test scratch directory.
+fn check_golden(name: &str, pixels: Vec<u8>, width: u32, height: u32) {
+    let path = golden_path(name);
+    let actual = image::RgbaImage::from_raw(width, height, pixels).unwrap();
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        actual.save(&path).unwrap();
+        eprintln!("updated {}", path.display());
+        return;
+    }
+    let expected = match image::open(&path) {
+        Ok(image) => image.to_rgba8(),
+        Err(e) => panic!("{}: {}, run with UPDATE_GOLDEN=1 to create it", path.display(), e),
+    };
+    assert_eq!(expected.dimensions(), actual.dimensions(), "{} has a different size", path.display());
+
+    let mut diff = image::RgbaImage::new(width, height);
+    let mut mismatched = 0;
+    let mut max_difference = 0;
+    for ((e, a), d) in expected.pixels().zip(actual.pixels()).zip(diff.pixels_mut()) {
+        let difference = e.0.iter().zip(a.0.iter()).map(|(x, y)| x.abs_diff(*y)).max().unwrap();
+        max_difference = max_difference.max(difference);
+        if difference > TOLERANCE {
+            mismatched += 1;
+            *d = image::Rgba([255, 0, 0, 255]);
+        } else {
+            *d = image::Rgba([e[0] / 4, e[1] / 4, e[2] / 4, 255]);
+        }
+    }
+    if mismatched > MAX_MISMATCHED {
+        let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+        let actual_path = dir.join(format!("{}_actual.png", name));
+        let diff_path = dir.join(format!("{}_diff.png", name));
+        actual.save(&actual_path).unwrap();
+        diff.save(&diff_path).unwrap();
+        panic!(
+            "{} pixels of {} differ from {} by more than {} (largest difference {}), see {} and {}",
+            mismatched, name, path.display(), TOLERANCE, max_difference, actual_path.display(), diff_path.display(),
+        );
+    }
+}
+
+#[test]
+#[ignore = "needs a GPU or software adapter, run with --ignored"]
+fn triangle() {
+    let inputs = common::Inputs {
+        file: "shader.wgsl",
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../examples/01/shader.wgsl"))),
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        strip_index_format: None,
+        shader_path: None,
+    };
+    let pixels = pollster::block_on(common::run_headless(inputs, 3, SIZE, SIZE, &wgpu_config())).unwrap();
+    check_golden("triangle", pixels, SIZE, SIZE);
+}
+
+#[test]
+#[ignore = "needs a GPU or software adapter, run with --ignored"]
+fn boids() {
+    let (device, queue) = device(&wgpu_config());
+    let particles = boids_sim::initial_particles(BOIDS_PARTICLES, BOIDS_SEED);
+    let mut sim = boids_sim::Simulation::new(
+        &device,
+        &queue,
+        boids_sim::SimParams::default(),
+        &particles,
+        boids_sim::Simulation::DEFAULT_WORKGROUP_SIZE,
+    );
+    let species = boids_sim::SpeciesConfig::default();
+    let renderer = boids_render::Renderer::new(
+        &device,
+        common::HEADLESS_FORMAT,
+        boids_render::RenderParams::new(0.1, species.colors()),
+    );
+    let capture = screenshot::Capture::new(&device, SIZE, SIZE, common::HEADLESS_FORMAT);
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    for _ in 0..BOIDS_STEPS {
+        sim.step(&mut encoder);
+    }
+    renderer.draw(&mut encoder, &capture.view, &sim);
+    queue.submit(Some(encoder.finish()));
+
+    let pixels = pollster::block_on(capture.read_rgba(&device, &queue)).unwrap();
+    check_golden("boids", pixels, SIZE, SIZE);
+}
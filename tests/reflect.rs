@@ -0,0 +1,129 @@
+//! Checks the layouts reflected from the example shaders and the Rust-side buffer sizes
+//! against them, which needs no GPU.
+#![allow(dead_code)]
+#[path="../examples/13/boids_render.rs"]
+mod boids_render;
+#[path="../examples/13/boids_sim.rs"]
+mod boids_sim;
+#[path="../examples/common/compute.rs"]
+mod compute;
+#[path="../examples/common/reflect.rs"]
+mod reflect;
+#[path="../examples/common/validate.rs"]
+mod validate;
+
+use std::mem;
+use reflect::Reflection;
+
+fn boids(entry_points: &[&str]) -> Reflection {
+    Reflection::new("boids.wgsl", include_str!("../examples/13/boids.wgsl"), entry_points).unwrap()
+}
+
+#[test]
+fn boids_buffer_sizes() {
+    let grid_entry_points = ["grid_clear", "grid_count", "grid_scan", "grid_scatter", "cs_grid_main"];
+    let grid = Reflection::new("boids_grid.wgsl", include_str!("../examples/13/boids_grid.wgsl"), &grid_entry_points).unwrap();
+    let params = boids_sim::GridParams::for_params(&boids_sim::SimParams::default());
+    boids_sim::Simulation::check_buffer_sizes(&boids(&["cs_main"]), 1000).unwrap();
+    boids_sim::Grid::check_buffer_sizes(&grid, &params, 1000).unwrap();
+    let render = boids(&["vs_main", "fs_main"]);
+    render.check_buffer_size(0, 0, mem::size_of::<boids_render::RenderParams>() as u64).unwrap();
+}
+
+#[test]
+fn size_mismatch() {
+    let compute = boids(&["cs_main"]);
+    let error = compute.check_buffer_size(0, 0, 4).unwrap_err().to_string();
+    assert!(error.contains("`params` (group 0, binding 0)"), "{}", error);
+    let error = compute.check_buffer_size(0, 1, 100).unwrap_err().to_string();
+    assert!(error.contains("at least one element of 16"), "{}", error);
+    // the fixed part alone, with an empty runtime-sized array
+    let error = compute.check_buffer_size(0, 1, 0).unwrap_err().to_string();
+    assert!(error.contains("at least one element of 16"), "{}", error);
+    compute.check_buffer_size(0, 1, 16).unwrap();
+    assert!(compute.check_buffer_size(3, 0, 4).is_err());
+}
+
+#[test]
+fn compute_layout() {
+    let groups = boids(&["cs_main"]).bind_group_layout_entries().unwrap();
+    assert_eq!(groups.len(), 3);
+    assert_eq!(groups.iter().map(Vec::len).collect::<Vec<_>>(), [4, 2, 2]);
+    assert!(groups.iter().flatten().all(|entry| entry.visibility == wgpu::ShaderStages::COMPUTE));
+    assert_eq!(
+        groups[0][2].ty,
+        wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(16),
+        },
+    );
+    assert_eq!(
+        groups[1][1].ty,
+        wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+    );
+}
+
+#[test]
+fn render_layout() {
+    let groups = boids(&["vs_main", "fs_main"]).bind_group_layout_entries().unwrap();
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].len(), 1);
+    assert_eq!(groups[0][0].visibility, wgpu::ShaderStages::FRAGMENT);
+}
+
+#[test]
+fn missing_entry_point() {
+    let error = Reflection::new("boids.wgsl", include_str!("../examples/13/boids.wgsl"), &["main"]).err().unwrap();
+    assert_eq!(error.to_string(), "boids.wgsl has no entry point `main`");
+}
+
+#[test]
+fn vertex_attributes() {
+    let render = boids(&["vs_main", "fs_main"]);
+    let (attributes, stride) = render.vertex_attributes(&[0, 1]).unwrap();
+    assert_eq!(attributes, wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2]);
+    assert_eq!(stride, 16);
+    let (attributes, stride) = render.vertex_attributes(&[3]).unwrap();
+    assert_eq!(attributes, wgpu::vertex_attr_array![3 => Uint32]);
+    assert_eq!(stride, 4);
+    assert!(render.vertex_attributes(&[4]).is_err());
+    assert!(boids(&["cs_main"]).vertex_attributes(&[0]).is_err());
+}
+
+#[test]
+fn compute_job_bindings() {
+    let rotate2d = |angle: &dyn Fn(compute::ComputeJob) -> compute::ComputeJob| {
+        let job = compute::ComputeJob::new("rotate2d.wgsl", include_str!("../examples/13/rotate2d.wgsl"), "main")
+            .input(&[[1.0f32, 0.0]; 3]);
+        angle(job).output::<[f32; 2]>(3).layout_entries()
+    };
+    let entries = rotate2d(&|job| job.uniform(&45.0f32)).unwrap();
+    assert_eq!(entries.len(), 3);
+    assert!(rotate2d(&|job| job.uniform(&45.0f64)).unwrap_err().to_string().contains("`angle_data`"));
+    assert!(rotate2d(&|job| job.input(&[45.0f32])).is_err());
+    assert!(rotate2d(&|job| job).is_err());
+
+    let empty = compute::ComputeJob::new("rotate2d.wgsl", include_str!("../examples/13/rotate2d.wgsl"), "main")
+        .input::<[f32; 2]>(&[])
+        .uniform(&45.0f32)
+        .output::<[f32; 2]>(0);
+    assert!(empty.layout_entries().unwrap_err().to_string().contains("at least one element"));
+}
+
+#[test]
+fn compute_job_work_group_count() {
+    let rotate2d = || compute::ComputeJob::new("rotate2d.wgsl", include_str!("../examples/13/rotate2d.wgsl"), "main");
+    let job = |invocations, workgroup_size| rotate2d().dispatch(invocations).workgroup_size(workgroup_size).work_group_count();
+    assert_eq!(job(100, 64).unwrap(), 2);
+    assert_eq!(job(100, 32).unwrap(), 4);
+    // the shader's own `#define WORKGROUP_SIZE 64`
+    assert_eq!(rotate2d().dispatch(100).work_group_count().unwrap(), 2);
+    assert!(job(100, 0).unwrap_err().to_string().contains("at least 1"));
+    assert_eq!(job(65535 * 64, 64).unwrap(), 65535);
+    assert!(job(65535 * 64 + 1, 64).unwrap_err().to_string().contains("more than the 65535"));
+}
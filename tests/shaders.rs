@@ -0,0 +1,77 @@
+//! Validates the WGSL of every example with naga, which needs no GPU, and checks where the
+//! diagnostics for broken shaders point.
+#[path="../examples/common/validate.rs"]
+mod validate;
+
+use validate::preprocess::Preprocessor;
+
+#[test]
+fn examples_validate() {
+    for (file, source) in validate::SHADERS {
+        if let Err(diagnostic) = validate::validate(file, source) {
+            panic!("{}", diagnostic);
+        }
+    }
+}
+
+#[test]
+fn parse_error_location() {
+    let source = "[[stage(compute), workgroup_size(1)]]\nfn main() {\n    let x = 1.0 +;\n}\n";
+    let diagnostic = validate::validate("broken.wgsl", source).unwrap_err();
+    assert_eq!((diagnostic.line, diagnostic.column), (3, 18));
+    assert_eq!(diagnostic.snippet, "  |\n3 |     let x = 1.0 +;\n  |                  ^");
+    assert!(diagnostic.to_string().starts_with("error: expected expression, found ';'\n --> broken.wgsl:3:18\n"));
+}
+
+#[test]
+fn validation_error_points_at_expression() {
+    let source = "[[stage(compute), workgroup_size(1)]]\nfn main() {\n    let x = 1.0 + 1u;\n}\n";
+    let diagnostic = validate::validate("broken.wgsl", source).unwrap_err();
+    assert_eq!((diagnostic.line, diagnostic.column), (3, 13));
+    assert!(diagnostic.snippet.ends_with("|             ^^^^^^^^"), "{}", diagnostic.snippet);
+    assert!(diagnostic.message.contains("Operation Add can't work with"), "{}", diagnostic.message);
+}
+
+#[test]
+fn validation_error_points_at_return_value() {
+    let source = "fn helper() -> f32 {\n    return 1u;\n}\n";
+    let diagnostic = validate::validate("broken.wgsl", source).unwrap_err();
+    assert_eq!((diagnostic.line, diagnostic.column), (2, 12));
+    assert!(diagnostic.message.starts_with("Function [1] 'helper' is invalid"), "{}", diagnostic.message);
+}
+
+#[test]
+fn preprocessor_includes_once_and_defines() {
+    let source = "#include \"common/math.wgsl\"\n#include \"common/math.wgsl\"\n#define SIZE 64\n[[stage(compute), workgroup_size(SIZE)]]\nfn main() {\n    let x = rotate2d(vec2<f32>(1.0, 0.0), PI);\n    let y = 1e2;\n}\n";
+    let preprocessed = Preprocessor::new().run("main.wgsl", source).unwrap();
+    assert_eq!(preprocessed.source.matches("fn rotate2d").count(), 1);
+    assert!(preprocessed.source.contains("workgroup_size(64)") && preprocessed.source.contains("1e2"));
+    let pi = preprocessed.source.lines().position(|line| line.starts_with("let PI")).unwrap() + 1;
+    assert_eq!(preprocessed.origin(pi), ("common/math.wgsl", 3));
+    let main = preprocessed.source.lines().position(|line| line.starts_with("fn main")).unwrap() + 1;
+    assert_eq!(preprocessed.origin(main), ("main.wgsl", 5));
+
+    let preprocessed = Preprocessor::new().define("SIZE", 128).run("main.wgsl", source).unwrap();
+    assert!(preprocessed.source.contains("workgroup_size(128)"));
+    validate::validate_with(&Preprocessor::new().define("SIZE", 128), "main.wgsl", source).unwrap();
+}
+
+#[test]
+fn diagnostics_point_at_the_original_lines() {
+    let source = "#include \"common/math.wgsl\"\n\nfn main() {\n    let x = PI + 1u;\n}\n";
+    let diagnostic = validate::validate("main.wgsl", source).unwrap_err();
+    assert_eq!((diagnostic.file.as_str(), diagnostic.line, diagnostic.column), ("main.wgsl", 4, 13), "{}", diagnostic);
+    assert!(diagnostic.snippet.contains("4 |     let x = PI + 1u;"), "{}", diagnostic.snippet);
+
+    let diagnostic = validate::validate("main.wgsl", "\n#include \"common/maths.wgsl\"\n").unwrap_err();
+    assert_eq!((diagnostic.line, diagnostic.column), (2, 1));
+    assert!(diagnostic.to_string().starts_with("error: no shader snippet \"common/maths.wgsl\" to include\n --> main.wgsl:2:1\n"), "{}", diagnostic);
+}
+
+#[test]
+fn includes_can_be_read_from_disk() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let source = "#include \"common/math.wgsl\"\n";
+    let preprocessed = Preprocessor::new().include_dir(dir).run("main.wgsl", source).unwrap();
+    assert_eq!(preprocessed.source.trim(), include_str!("../examples/common/math.wgsl").trim());
+}
@@ -0,0 +1,153 @@
+#![allow(dead_code)]
+#[path="../examples/common/transforms.rs"]
+mod transforms;
+
+use cgmath::{ortho, perspective, prelude::*, Deg, Matrix4, Point3, Quaternion, Rad, Vector3, Vector4};
+use transforms::{Projection, Transform, OPENGL_TO_WGPU_MATRIX};
+
+const EPSILON: f32 = 1e-5;
+
+/// Clip space depth of the view space point `distance` units in front of the camera.
+fn depth(projection: Matrix4<f32>, distance: f32) -> f32 {
+    let clip = projection * Vector4::new(0.0, 0.0, -distance, 1.0);
+    clip.z / clip.w
+}
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < EPSILON, "{} is not {}", actual, expected);
+}
+
+#[test]
+fn opengl_to_wgpu_maps_depth_to_zero_one() {
+    let near = OPENGL_TO_WGPU_MATRIX * Vector4::new(0.0, 0.0, -1.0, 1.0);
+    let far = OPENGL_TO_WGPU_MATRIX * Vector4::new(0.0, 0.0, 1.0, 1.0);
+    assert_close(near.z / near.w, 0.0);
+    assert_close(far.z / far.w, 1.0);
+}
+
+#[test]
+fn perspective_maps_near_and_far_to_zero_and_one() {
+    let projection = Projection::perspective(Deg(60.0), 0.5, 50.0).matrix(1.5);
+    assert_close(depth(projection, 0.5), 0.0);
+    assert_close(depth(projection, 50.0), 1.0);
+}
+
+#[test]
+fn reverse_z_maps_near_and_far_to_one_and_zero() {
+    let projection = Projection::perspective(Deg(60.0), 0.5, 50.0).with_reverse_z().matrix(1.5);
+    assert_close(depth(projection, 0.5), 1.0);
+    assert_close(depth(projection, 50.0), 0.0);
+    assert_eq!(Projection::default().with_reverse_z().far_depth(), 0.0);
+}
+
+#[test]
+fn infinite_perspective_approaches_the_far_end() {
+    let projection = Projection::infinite_perspective(Deg(60.0), 0.5).matrix(1.5);
+    assert_close(depth(projection, 0.5), 0.0);
+    let distant = depth(projection, 1e6);
+    assert!(distant < 1.0 && distant > 1.0 - 1e-5, "{}", distant);
+
+    let reversed = Projection::infinite_perspective(Deg(60.0), 0.5).with_reverse_z().matrix(1.5);
+    assert_close(depth(reversed, 0.5), 1.0);
+    let distant = depth(reversed, 1e6);
+    assert!(distant > 0.0 && distant < 1e-5, "{}", distant);
+}
+
+#[test]
+fn infinite_perspective_matches_a_distant_far_plane() {
+    let infinite = Projection::infinite_perspective(Deg(45.0), 0.1).matrix(2.0);
+    let finite = Projection::perspective(Deg(45.0), 0.1, 1e7).matrix(2.0);
+    for distance in [0.1, 1.0, 10.0, 100.0] {
+        assert_close(depth(infinite, distance), depth(finite, distance));
+    }
+}
+
+#[test]
+fn orthographic_extent_follows_aspect() {
+    let projection = Projection::orthographic(4.0, 1.0, 11.0).matrix(2.0);
+    let corner = projection * Vector4::new(4.0, 2.0, -1.0, 1.0);
+    assert_close(corner.x, 1.0);
+    assert_close(corner.y, 1.0);
+    assert_close(corner.z, 0.0);
+    assert_close(depth(projection, 11.0), 1.0);
+}
+
+#[test]
+fn create_projection_keeps_its_defaults() {
+    for aspect in [4.0 / 3.0, 16.0 / 9.0] {
+        assert_eq!(
+            transforms::create_projection(aspect, true),
+            OPENGL_TO_WGPU_MATRIX * perspective(Rad(2.0 * std::f32::consts::PI / 5.0), aspect, 0.1, 100.0),
+        );
+        assert_eq!(
+            transforms::create_projection(aspect, false),
+            OPENGL_TO_WGPU_MATRIX * ortho(-4.0, 4.0, -3.0, 3.0, -1.0, 6.0),
+        );
+    }
+}
+
+fn assert_matrix_close(actual: Matrix4<f32>, expected: Matrix4<f32>) {
+    let a: &[f32; 16] = actual.as_ref();
+    let e: &[f32; 16] = expected.as_ref();
+    assert!(a.iter().zip(e).all(|(a, e)| (a - e).abs() < 1e-4), "{:?} is not {:?}", actual, expected);
+}
+
+#[test]
+fn transform_from_euler_matches_create_transforms() {
+    let (translation, rotation, scaling) = ([1.0, -2.0, 3.0], [0.3, -1.2, 2.0], [2.0, 0.5, 1.5]);
+    assert_matrix_close(
+        Transform::from_euler(translation, rotation, scaling).matrix(),
+        transforms::create_transforms(translation, rotation, scaling),
+    );
+}
+
+#[test]
+fn transform_compose_matches_matrix_product() {
+    let a = Transform::from_euler([1.0, 2.0, 3.0], [0.5, 0.2, -0.7], [2.0, 2.0, 2.0]);
+    let b = Transform::from_euler([-3.0, 0.5, 1.0], [-1.0, 0.4, 1.1], [0.5, 1.0, 3.0]);
+    assert_matrix_close((a * b).matrix(), a.matrix() * b.matrix());
+    let p = Point3::new(0.3, -0.8, 2.5);
+    let q = (a * b).transform_point(p);
+    let r = a.transform_point(b.transform_point(p));
+    assert!((q - r).magnitude() < 1e-4, "{:?} is not {:?}", q, r);
+}
+
+#[test]
+fn transform_inverse_undoes_it() {
+    let t = Transform::from_euler([1.0, 2.0, 3.0], [0.5, 0.2, -0.7], [3.0, 3.0, 3.0]);
+    assert_matrix_close((t * t.inverse()).matrix(), Matrix4::identity());
+    assert_matrix_close((t.inverse() * t).matrix(), Matrix4::identity());
+    assert_matrix_close(t.inverse().matrix(), t.matrix().invert().unwrap());
+}
+
+#[test]
+fn transform_decomposes_matrices() {
+    let t = Transform::from_euler([1.0, -2.0, 3.0], [0.3, -1.2, 2.0], [2.0, 0.5, 1.5]);
+    let d = Transform::from_matrix(t.matrix()).unwrap();
+    assert!((d.translation - t.translation).magnitude() < 1e-5);
+    assert!((d.scale - t.scale).magnitude() < 1e-5);
+    assert!(d.rotation.dot(t.rotation).abs() > 1.0 - 1e-5);
+
+    let mirrored = Matrix4::from_nonuniform_scale(-1.0, 2.0, 1.0) * Matrix4::from_angle_y(Deg(30.0));
+    let d = Transform::from_matrix(mirrored).unwrap();
+    assert!(d.scale.x < 0.0);
+    assert_matrix_close(d.matrix(), mirrored);
+
+    assert_eq!(Transform::from_matrix(Matrix4::from_nonuniform_scale(1.0, 0.0, 1.0)), None);
+}
+
+#[test]
+fn transform_interpolates() {
+    let a = Transform::new(Vector3::zero(), Quaternion::one(), Vector3::new(1.0, 1.0, 1.0));
+    let b = Transform::new(
+        Vector3::new(2.0, 0.0, -4.0),
+        Quaternion::from_angle_y(Deg(90.0)),
+        Vector3::new(3.0, 3.0, 3.0),
+    );
+    let half = a.slerp(&b, 0.5);
+    assert!((half.translation - Vector3::new(1.0, 0.0, -2.0)).magnitude() < 1e-5);
+    assert!((half.scale - Vector3::new(2.0, 2.0, 2.0)).magnitude() < 1e-5);
+    assert!(half.rotation.dot(Quaternion::from_angle_y(Deg(45.0))) > 1.0 - 1e-5);
+    assert_eq!(a.lerp(&b, 0.0), a);
+    assert!(a.lerp(&b, 1.0).rotation.dot(b.rotation) > 1.0 - 1e-5);
+}